@@ -0,0 +1,496 @@
+use std::{
+    io::{self, Write},
+    str::FromStr,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use itertools::Itertools;
+
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const MIN_FPS: f64 = 0.5;
+const MAX_FPS: f64 = 480.0;
+
+/// One rendered state of a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    pub(crate) text: String,
+    /// (line, column) in `text` the viewport should keep in view
+    pub(crate) focus: Option<(usize, usize)>,
+}
+
+impl Frame {
+    pub(crate) fn new(text: String) -> Self {
+        Frame { text, focus: None }
+    }
+
+    pub(crate) fn focused(text: String, focus: (usize, usize)) -> Self {
+        Frame {
+            text,
+            focus: Some(focus),
+        }
+    }
+
+    fn dim(&self) -> (usize, usize) {
+        let lines = self.text.lines();
+        let cols = lines.clone().map(|line| line.chars().count()).max();
+        (lines.count(), cols.unwrap_or(0))
+    }
+}
+
+/// Collects frames from a simulation, keeping only every `stride`-th one so long runs stay
+/// watchable. Frames are rendered lazily, so skipped steps cost nothing
+pub(crate) struct Recorder {
+    frames: Vec<Frame>,
+    stride: usize,
+    /// Steps left before the next frame is kept
+    until_next: usize,
+    kept_last: bool,
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Recorder::with_stride(1)
+    }
+
+    pub(crate) fn with_stride(stride: usize) -> Self {
+        Recorder {
+            frames: Vec::new(),
+            stride: stride.max(1),
+            until_next: 0,
+            kept_last: false,
+        }
+    }
+
+    pub(crate) fn record(&mut self, render: impl FnOnce() -> Frame) {
+        self.kept_last = self.until_next == 0;
+        if self.kept_last {
+            self.frames.push(render());
+            self.until_next = self.stride;
+        }
+        self.until_next -= 1;
+    }
+
+    /// Records the end state regardless of the stride, unless it was the last frame kept
+    pub(crate) fn record_final(&mut self, render: impl FnOnce() -> Frame) {
+        if !self.kept_last {
+            self.frames.push(render());
+        }
+    }
+
+    pub(crate) fn into_frames(self) -> Vec<Frame> {
+        self.frames
+    }
+}
+
+/// Window of the frame that is drawn, scrolling along with the frame's focus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Viewport {
+    pub(crate) rows: usize,
+    pub(crate) cols: usize,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            rows: 40,
+            cols: 120,
+        }
+    }
+}
+
+/// New start of a window `size` long so `focus` is not within a quarter of either edge
+fn follow(start: usize, focus: usize, size: usize) -> usize {
+    let margin = size / 4;
+    if focus < start + margin {
+        focus.saturating_sub(margin)
+    } else if focus + margin >= start + size {
+        focus + margin + 1 - size
+    } else {
+        start
+    }
+}
+
+impl Viewport {
+    /// Top left corner to draw `frame` from, given where the previous frame was drawn from
+    fn origin(&self, frame: &Frame, previous: (usize, usize)) -> (usize, usize) {
+        let (rows, cols) = frame.dim();
+        let (row, col) = match frame.focus {
+            Some((row, col)) => (
+                follow(previous.0, row, self.rows),
+                follow(previous.1, col, self.cols),
+            ),
+            None => previous,
+        };
+        (
+            row.min(rows.saturating_sub(self.rows)),
+            col.min(cols.saturating_sub(self.cols)),
+        )
+    }
+
+    fn crop(&self, frame: &Frame, origin: (usize, usize)) -> String {
+        frame
+            .text
+            .lines()
+            .skip(origin.0)
+            .take(self.rows)
+            .map(|line| {
+                line.chars()
+                    .skip(origin.1)
+                    .take(self.cols)
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+}
+
+/// Playback controls, read one per line from stdin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Command {
+    TogglePause,
+    Step,
+    Back,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim() {
+            "" | "p" => Command::TogglePause,
+            "s" | "n" => Command::Step,
+            "b" => Command::Back,
+            "+" => Command::Faster,
+            "-" => Command::Slower,
+            "q" => Command::Quit,
+            other => bail!("Unknown playback command {other:?}"),
+        })
+    }
+}
+
+/// Sends every command typed on stdin, until stdin closes
+pub(crate) fn stdin_commands() -> Receiver<Command> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if let Ok(command) = line.parse() {
+                if tx.send(command).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Where playback is, separate from any drawing or timing
+#[derive(Debug, Clone, PartialEq)]
+struct Playback {
+    frame: usize,
+    len: usize,
+    fps: f64,
+    paused: bool,
+    done: bool,
+}
+
+impl Playback {
+    fn new(len: usize, fps: f64) -> Self {
+        Playback {
+            frame: 0,
+            len,
+            fps: fps.clamp(MIN_FPS, MAX_FPS),
+            paused: false,
+            done: len == 0,
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::TogglePause => self.paused = !self.paused,
+            Command::Step => {
+                self.paused = true;
+                self.frame = (self.frame + 1).min(self.len - 1);
+            }
+            Command::Back => {
+                self.paused = true;
+                self.frame = self.frame.saturating_sub(1);
+            }
+            Command::Faster => self.fps = (self.fps * 2.0).min(MAX_FPS),
+            Command::Slower => self.fps = (self.fps / 2.0).max(MIN_FPS),
+            Command::Quit => self.done = true,
+        }
+    }
+
+    /// Moves on to the next frame unless paused, finishing after the last one
+    fn tick(&mut self) {
+        if self.paused {
+            return;
+        }
+        if self.frame + 1 < self.len {
+            self.frame += 1;
+        } else {
+            self.done = true;
+        }
+    }
+
+    fn delay(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps)
+    }
+
+    fn status(&self) -> String {
+        format!(
+            "frame {}/{} at {} fps{}  [enter] pause  [s]tep  [b]ack  [+/-] speed  [q]uit",
+            self.frame + 1,
+            self.len,
+            self.fps,
+            if self.paused { " (paused)" } else { "" }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Player {
+    pub(crate) fps: f64,
+    pub(crate) viewport: Viewport,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player {
+            fps: 20.0,
+            viewport: Viewport::default(),
+        }
+    }
+}
+
+impl Player {
+    pub(crate) fn play(
+        &self,
+        frames: &[Frame],
+        out: &mut impl Write,
+        commands: &Receiver<Command>,
+    ) -> io::Result<()> {
+        let mut playback = Playback::new(frames.len(), self.fps);
+        let mut origin = (0, 0);
+        while !playback.done {
+            let frame = &frames[playback.frame];
+            origin = self.viewport.origin(frame, origin);
+            writeln!(
+                out,
+                "{CLEAR}{}\n{}",
+                self.viewport.crop(frame, origin),
+                playback.status()
+            )?;
+            out.flush()?;
+
+            if playback.paused {
+                match commands.recv() {
+                    Ok(command) => playback.apply(command),
+                    // nothing can unpause us anymore
+                    Err(_) => playback.paused = false,
+                }
+            } else {
+                match commands.recv_timeout(playback.delay()) {
+                    Ok(command) => playback.apply(command),
+                    Err(RecvTimeoutError::Timeout) => playback.tick(),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        thread::sleep(playback.delay());
+                        playback.tick();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runner settings for `--animate [DAY...] [--fps N] [--size ROWSxCOLS]`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Options {
+    /// Days to animate, all of them if empty
+    pub(crate) days: Vec<u32>,
+    pub(crate) player: Player,
+}
+
+impl Options {
+    /// `None` unless `--animate` was passed
+    pub(crate) fn from_args(args: &[String]) -> Result<Option<Self>> {
+        if !args.iter().any(|arg| arg == "--animate") {
+            return Ok(None);
+        }
+        let mut options = Options {
+            days: Vec::new(),
+            player: Player::default(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--animate" => (),
+                "--fps" => {
+                    let fps = args.next().context("--fps needs a value")?;
+                    options.player.fps = fps.parse().with_context(|| format!("Bad fps {fps}"))?;
+                    // `parse` accepts NaN and inf, which make no frame delay
+                    ensure!(
+                        options.player.fps.is_finite() && options.player.fps > 0.0,
+                        "Fps should be a positive number, got {fps}"
+                    );
+                }
+                "--size" => {
+                    let size = args.next().context("--size needs a value")?;
+                    let (rows, cols) = size
+                        .split_once('x')
+                        .ok_or_else(|| anyhow!("Size should look like 40x120, got {size}"))?;
+                    options.player.viewport = Viewport {
+                        rows: rows.parse()?,
+                        cols: cols.parse()?,
+                    };
+                }
+                day => options.days.push(
+                    day.parse()
+                        .with_context(|| format!("Unknown argument {day}"))?,
+                ),
+            }
+        }
+        Ok(Some(options))
+    }
+}
+
+#[test]
+fn recorder_stride() {
+    let mut recorder = Recorder::with_stride(3);
+    for i in 0..7 {
+        recorder.record(|| Frame::new(i.to_string()));
+    }
+    recorder.record_final(|| Frame::new("end".to_owned()));
+    let texts = recorder
+        .into_frames()
+        .into_iter()
+        .map(|f| f.text)
+        .collect_vec();
+    assert_eq!(texts, ["0", "3", "6"]);
+
+    let mut recorder = Recorder::with_stride(3);
+    for i in 0..8 {
+        recorder.record(|| Frame::new(i.to_string()));
+    }
+    recorder.record_final(|| Frame::new("end".to_owned()));
+    let texts = recorder
+        .into_frames()
+        .into_iter()
+        .map(|f| f.text)
+        .collect_vec();
+    assert_eq!(texts, ["0", "3", "6", "end"]);
+}
+
+#[test]
+fn viewport_follows_focus() {
+    let text = (0..10).map(|row| format!("{row}abcdefghi")).join("\n");
+    let viewport = Viewport { rows: 4, cols: 4 };
+
+    let frame = Frame::new(text.clone());
+    assert_eq!(viewport.origin(&frame, (0, 0)), (0, 0));
+    assert_eq!(viewport.crop(&frame, (0, 0)), "0abc\n1abc\n2abc\n3abc");
+
+    // stays put while the focus is away from the edges
+    let frame = Frame::focused(text.clone(), (2, 1));
+    assert_eq!(viewport.origin(&frame, (0, 0)), (0, 0));
+    let frame = Frame::focused(text.clone(), (3, 5));
+    assert_eq!(viewport.origin(&frame, (0, 0)), (1, 3));
+    assert_eq!(viewport.crop(&frame, (1, 3)), "cdef\ncdef\ncdef\ncdef");
+    // but not past the frame
+    let frame = Frame::focused(text, (9, 9));
+    assert_eq!(viewport.origin(&frame, (1, 3)), (6, 6));
+}
+
+#[test]
+fn playback_commands() {
+    let commands = ["", "s", "s", "b", "+", "-", "-", "q", "x"]
+        .iter()
+        .map(|s| s.parse::<Command>().ok())
+        .collect_vec();
+    assert_eq!(
+        commands,
+        [
+            Some(Command::TogglePause),
+            Some(Command::Step),
+            Some(Command::Step),
+            Some(Command::Back),
+            Some(Command::Faster),
+            Some(Command::Slower),
+            Some(Command::Slower),
+            Some(Command::Quit),
+            None
+        ]
+    );
+
+    let mut playback = Playback::new(3, 10.0);
+    playback.tick();
+    assert_eq!(playback.frame, 1);
+    playback.apply(Command::TogglePause);
+    playback.tick();
+    assert_eq!(playback.frame, 1);
+    playback.apply(Command::Step);
+    playback.apply(Command::Step);
+    assert_eq!((playback.frame, playback.paused), (2, true));
+    playback.apply(Command::Back);
+    playback.apply(Command::Slower);
+    assert_eq!((playback.frame, playback.fps), (1, 5.0));
+    playback.apply(Command::TogglePause);
+    playback.tick();
+    assert!(!playback.done);
+    playback.tick();
+    assert!(playback.done);
+}
+
+#[test]
+fn play_until_quit() {
+    let frames = ["ab\ncd", "ef\ngh", "ij\nkl"].map(|s| Frame::new(s.to_owned()));
+    let player = Player {
+        fps: MAX_FPS,
+        viewport: Viewport { rows: 1, cols: 1 },
+    };
+
+    let (tx, rx) = mpsc::channel();
+    drop(tx);
+    let mut out = Vec::new();
+    player.play(&frames, &mut out, &rx).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let drawn = out.split(CLEAR).skip(1).collect_vec();
+    assert_eq!(drawn.len(), 3);
+    assert!(drawn[1].starts_with("e\nframe 2/3"));
+
+    let (tx, rx) = mpsc::channel();
+    tx.send(Command::Step).unwrap();
+    tx.send(Command::Quit).unwrap();
+    let mut out = Vec::new();
+    player.play(&frames, &mut out, &rx).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.matches(CLEAR).count(), 2);
+    assert!(out.contains("frame 2/3 at 480 fps (paused)"));
+
+    let (_tx, rx) = mpsc::channel();
+    player.play(&[], &mut Vec::new(), &rx).unwrap();
+}
+
+#[test]
+fn options_from_args() {
+    let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+    assert_eq!(Options::from_args(&args("14 --fps 5")).unwrap(), None);
+    let options = Options::from_args(&args("--animate 14 --fps 5 --size 10x20"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.days, [14]);
+    assert_eq!(options.player.fps, 5.0);
+    for fps in ["NaN", "inf", "-inf", "0", "-3", "fast"] {
+        let line = format!("--animate --fps {fps}");
+        assert!(Options::from_args(&args(&line)).is_err(), "{fps}");
+    }
+}
@@ -0,0 +1,257 @@
+use itertools::Itertools;
+
+#[cfg(test)]
+use crate::utils::XorShift;
+
+pub(crate) type Point = (i64, i64);
+/// Inclusive range of coordinates
+pub(crate) type Interval = (i64, i64);
+
+pub(crate) fn manhattan(a: Point, b: Point) -> i64 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+/// Rotates by 45° into `u = x + y`, `v = x - y`, where diamonds become squares. Only points
+/// with `u` and `v` of the same parity map back to the grid.
+pub(crate) fn rotate(p: Point) -> Point {
+    (p.0 + p.1, p.0 - p.1)
+}
+
+/// Every point within `radius` of `center`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Diamond {
+    pub(crate) center: Point,
+    pub(crate) radius: i64,
+}
+
+impl Diamond {
+    pub(crate) fn new(center: Point, radius: i64) -> Self {
+        Diamond { center, radius }
+    }
+
+    /// The diamond around `center` that just reaches `edge`
+    pub(crate) fn reaching(center: Point, edge: Point) -> Self {
+        Diamond::new(center, manhattan(center, edge))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn contains(&self, p: Point) -> bool {
+        manhattan(self.center, p) <= self.radius
+    }
+
+    /// Inclusive x range covered on row `y`
+    pub(crate) fn row(&self, y: i64) -> Option<Interval> {
+        let reach = self.radius - (self.center.1 - y).abs();
+        (reach >= 0).then_some((self.center.0 - reach, self.center.0 + reach))
+    }
+
+    /// Inclusive `u` and `v` ranges of the rotated square
+    pub(crate) fn rotated(&self) -> (Interval, Interval) {
+        let (u, v) = rotate(self.center);
+        (
+            (u - self.radius, u + self.radius),
+            (v - self.radius, v + self.radius),
+        )
+    }
+}
+
+/// Sorts inclusive intervals and joins those that overlap or touch
+pub(crate) fn merge_intervals(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_unstable();
+    let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for (lo, hi) in intervals {
+        match merged.last_mut() {
+            Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+            _ => merged.push((lo, hi)),
+        }
+    }
+    merged
+}
+
+/// Disjoint, sorted x ranges covered on row `y`
+pub(crate) fn row_intervals(diamonds: &[Diamond], y: i64) -> Vec<Interval> {
+    merge_intervals(diamonds.iter().filter_map(|d| d.row(y)).collect())
+}
+
+/// Cells on row `y` that are covered and are not one of the known `beacons`, so no other beacon
+/// can be there
+pub(crate) fn excluded_in_row(diamonds: &[Diamond], beacons: &[Point], y: i64) -> i64 {
+    let intervals = row_intervals(diamonds, y);
+    let covered: i64 = intervals.iter().map(|(lo, hi)| hi - lo + 1).sum();
+    let beacons = beacons
+        .iter()
+        .filter(|b| b.1 == y && intervals.iter().any(|(lo, hi)| (*lo..=*hi).contains(&b.0)))
+        .unique()
+        .count();
+    covered - beacons as i64
+}
+
+/// Splits the `u_span` × `v_span` part of the rotated plane along every square edge, giving
+/// inclusive cells that are each either entirely covered or entirely uncovered
+fn cells(
+    diamonds: &[Diamond],
+    u_span: Interval,
+    v_span: Interval,
+) -> Vec<(Interval, Interval, bool)> {
+    let squares = diamonds.iter().map(Diamond::rotated).collect_vec();
+    let cuts = |span: Interval, edges: &mut dyn Iterator<Item = Interval>| {
+        edges
+            .flat_map(|(lo, hi)| [lo, hi + 1])
+            .chain([span.0, span.1 + 1])
+            .filter(|cut| (span.0..=span.1 + 1).contains(cut))
+            .sorted_unstable()
+            .dedup()
+            .collect_vec()
+    };
+    let us = cuts(u_span, &mut squares.iter().map(|s| s.0));
+    let vs = cuts(v_span, &mut squares.iter().map(|s| s.1));
+
+    us.iter()
+        .tuple_windows()
+        .cartesian_product(vs.iter().tuple_windows().collect_vec())
+        .map(|((u0, u1), (v0, v1))| {
+            let (u, v) = ((*u0, u1 - 1), (*v0, v1 - 1));
+            let covered = squares
+                .iter()
+                .any(|(su, sv)| su.0 <= u.0 && u.1 <= su.1 && sv.0 <= v.0 && v.1 <= sv.1);
+            (u, v, covered)
+        })
+        .collect()
+}
+
+/// Number of values in `lo..=hi` with the given parity
+fn with_parity(lo: i64, hi: i64, parity: i64) -> i64 {
+    ((hi - parity).div_euclid(2) - (lo - 1 - parity).div_euclid(2)).max(0)
+}
+
+/// Number of grid points covered by at least one diamond
+pub(crate) fn coverage_area(diamonds: &[Diamond]) -> i64 {
+    let squares = diamonds.iter().map(Diamond::rotated).collect_vec();
+    let (Some(u_min), Some(u_max)) = (
+        squares.iter().map(|s| s.0 .0).min(),
+        squares.iter().map(|s| s.0 .1).max(),
+    ) else {
+        return 0;
+    };
+    let v_min = squares.iter().map(|s| s.1 .0).min().unwrap();
+    let v_max = squares.iter().map(|s| s.1 .1).max().unwrap();
+
+    cells(diamonds, (u_min, u_max), (v_min, v_max))
+        .into_iter()
+        .filter(|cell| cell.2)
+        .map(|(u, v, _)| {
+            (0..2)
+                .map(|parity| with_parity(u.0, u.1, parity) * with_parity(v.0, v.1, parity))
+                .sum::<i64>()
+        })
+        .sum()
+}
+
+/// Every point in the inclusive box from `min` to `max` that no diamond covers, sorted
+pub(crate) fn uncovered(diamonds: &[Diamond], min: Point, max: Point) -> Vec<Point> {
+    let ((x0, y0), (x1, y1)) = (min, max);
+    if x0 > x1 || y0 > y1 {
+        return Vec::new();
+    }
+
+    let mut points = Vec::new();
+    for ((ua, ub), (va, vb), _) in cells(diamonds, (x0 + y0, x1 + y1), (x0 - y1, x1 - y0))
+        .into_iter()
+        .filter(|cell| !cell.2)
+    {
+        // keep x = (u + v) / 2 and y = (u - v) / 2 inside the box
+        let u_lo = ua.max(va + 2 * y0).max(2 * x0 - vb);
+        let u_hi = ub.min(2 * x1 - va).min(vb + 2 * y1);
+        for u in u_lo..=u_hi {
+            let v_lo = va.max(u - 2 * y1).max(2 * x0 - u);
+            let v_hi = vb.min(u - 2 * y0).min(2 * x1 - u);
+            let v_lo = v_lo + (u - v_lo).rem_euclid(2);
+            points.extend((v_lo..=v_hi).step_by(2).map(|v| ((u + v) / 2, (u - v) / 2)));
+        }
+    }
+    points.sort_unstable();
+    points
+}
+
+#[cfg(test)]
+fn random_diamonds(rng: &mut XorShift) -> Vec<Diamond> {
+    (0..rng.below(6))
+        .map(|_| {
+            let center = (rng.below(21) as i64 - 10, rng.below(21) as i64 - 10);
+            Diamond::new(center, rng.below(7) as i64)
+        })
+        .collect()
+}
+
+#[test]
+fn single_diamonds() {
+    let dot = Diamond::new((3, -2), 0);
+    assert_eq!(dot.row(-2), Some((3, 3)));
+    assert_eq!(dot.row(-1), None);
+    assert_eq!(coverage_area(&[dot]), 1);
+
+    let diamond = Diamond::reaching((0, 0), (1, -2));
+    assert_eq!(diamond.radius, 3);
+    assert_eq!(diamond.row(1), Some((-2, 2)));
+    assert_eq!(coverage_area(&[diamond]), 25);
+    assert_eq!(coverage_area(&[diamond, diamond, dot]), 26);
+    assert_eq!(coverage_area(&[]), 0);
+
+    assert_eq!(
+        merge_intervals(vec![(5, 7), (0, 2), (3, 3), (1, 1), (9, 9)]),
+        [(0, 3), (5, 7), (9, 9)]
+    );
+    assert_eq!(
+        uncovered(&[diamond], (-3, -3), (-1, -1)),
+        [(-3, -3), (-3, -2), (-3, -1), (-2, -3), (-2, -2), (-1, -3)]
+    );
+    assert!(uncovered(&[diamond], (-1, -1), (1, 1)).is_empty());
+}
+
+#[test]
+fn random_against_brute_force() {
+    let mut rng = XorShift::new(47);
+    let grid = (-20..=20).cartesian_product(-20..=20).collect_vec();
+    for _ in 0..300 {
+        let diamonds = random_diamonds(&mut rng);
+        let is_covered = |p: Point| diamonds.iter().any(|d| d.contains(p));
+
+        let area = grid.iter().filter(|p| is_covered(**p)).count();
+        assert_eq!(coverage_area(&diamonds), area as i64, "{diamonds:?}");
+
+        let y = rng.below(31) as i64 - 15;
+        let row = (-20..=20).filter(|x| is_covered((*x, y))).collect_vec();
+        let intervals = row_intervals(&diamonds, y);
+        assert_eq!(
+            intervals
+                .iter()
+                .flat_map(|(lo, hi)| *lo..=*hi)
+                .collect_vec(),
+            row
+        );
+        assert!(intervals.windows(2).all(|w| w[0].1 + 1 < w[1].0));
+
+        let beacons = (0..rng.below(4))
+            .map(|_| (rng.below(21) as i64 - 10, y + rng.below(2) as i64))
+            .collect_vec();
+        let excluded = row.iter().filter(|x| !beacons.contains(&(**x, y))).count();
+        assert_eq!(excluded_in_row(&diamonds, &beacons, y), excluded as i64);
+
+        let min = (rng.below(21) as i64 - 15, rng.below(21) as i64 - 15);
+        let max = (
+            min.0 + rng.below(12) as i64 - 1,
+            min.1 + rng.below(12) as i64 - 1,
+        );
+        let open = grid
+            .iter()
+            .copied()
+            .filter(|p| (min.0..=max.0).contains(&p.0) && (min.1..=max.1).contains(&p.1))
+            .filter(|p| !is_covered(*p))
+            .collect_vec();
+        assert_eq!(
+            uncovered(&diamonds, min, max),
+            open,
+            "{diamonds:?} {min:?} {max:?}"
+        );
+    }
+}
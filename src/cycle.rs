@@ -0,0 +1,243 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::Hash,
+};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CycleMethod {
+    /// Remembers the first step every key was seen at, fast but keeps every key
+    Table,
+    /// Tortoise and hare, constant memory
+    Floyd,
+    /// Power-of-two teleporting tortoise, constant memory and fewer steps than Floyd
+    Brent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cycle {
+    /// Number of steps before the cycle is entered
+    pub(crate) start: usize,
+    pub(crate) length: usize,
+}
+
+/// Finds the cycle in the sequence of keys seen when repeatedly stepping `initial`, giving up
+/// if it has not shown up after about `limit` steps. Equal keys must mean that the following
+/// keys are equal too.
+pub(crate) fn find_cycle<S, K>(
+    initial: &S,
+    mut step: impl FnMut(&mut S),
+    key: impl Fn(&S) -> K,
+    method: CycleMethod,
+    limit: usize,
+) -> Option<Cycle>
+where
+    S: Clone,
+    K: Eq + Hash,
+{
+    match method {
+        CycleMethod::Table => {
+            let mut seen = HashMap::new();
+            let mut state = initial.clone();
+            for i in 0..=limit {
+                match seen.entry(key(&state)) {
+                    Entry::Occupied(e) => {
+                        return Some(Cycle {
+                            start: *e.get(),
+                            length: i - e.get(),
+                        })
+                    }
+                    Entry::Vacant(e) => {
+                        e.insert(i);
+                    }
+                }
+                step(&mut state);
+            }
+            None
+        }
+        CycleMethod::Floyd => {
+            let mut tortoise = initial.clone();
+            let mut hare = initial.clone();
+            let mut steps = 1;
+            step(&mut tortoise);
+            step(&mut hare);
+            step(&mut hare);
+            while key(&tortoise) != key(&hare) {
+                if steps > limit {
+                    return None;
+                }
+                step(&mut tortoise);
+                step(&mut hare);
+                step(&mut hare);
+                steps += 1;
+            }
+
+            let mut start = 0;
+            tortoise = initial.clone();
+            while key(&tortoise) != key(&hare) {
+                step(&mut tortoise);
+                step(&mut hare);
+                start += 1;
+            }
+
+            let mut length = 1;
+            hare = tortoise.clone();
+            step(&mut hare);
+            while key(&tortoise) != key(&hare) {
+                step(&mut hare);
+                length += 1;
+            }
+            Some(Cycle { start, length })
+        }
+        CycleMethod::Brent => {
+            let mut power = 1;
+            let mut length = 1;
+            let mut steps = 1;
+            let mut tortoise = initial.clone();
+            let mut hare = initial.clone();
+            step(&mut hare);
+            while key(&tortoise) != key(&hare) {
+                // the hare has caught up within twice the cycle's end if there is one
+                if steps > 2 * limit {
+                    return None;
+                }
+                if power == length {
+                    tortoise = hare.clone();
+                    power *= 2;
+                    length = 0;
+                }
+                step(&mut hare);
+                length += 1;
+                steps += 1;
+            }
+
+            let mut start = 0;
+            tortoise = initial.clone();
+            hare = initial.clone();
+            for _ in 0..length {
+                step(&mut hare);
+            }
+            while key(&tortoise) != key(&hare) {
+                step(&mut tortoise);
+                step(&mut hare);
+                start += 1;
+            }
+            Some(Cycle { start, length })
+        }
+    }
+}
+
+/// `measure` after `n` steps, only simulating until the first repeat and extrapolating the
+/// growth per cycle from there. The growth may be negative as long as the measure itself
+/// stays positive.
+pub(crate) fn extrapolate<S, K>(
+    initial: &S,
+    n: usize,
+    mut step: impl FnMut(&mut S),
+    key: impl Fn(&S) -> K,
+    measure: impl Fn(&S) -> usize,
+    method: CycleMethod,
+) -> usize
+where
+    S: Clone,
+    K: Eq + Hash,
+{
+    let cycle = find_cycle(initial, &mut step, key, method, n);
+    let mut state = initial.clone();
+    let direct = cycle.map_or(n, |cycle| n.min(cycle.start));
+    for _ in 0..direct {
+        step(&mut state);
+    }
+    let cycle = match cycle {
+        Some(cycle) if n > cycle.start => cycle,
+        _ => return measure(&state),
+    };
+
+    let cycle_start = state.clone();
+    for _ in 0..cycle.length {
+        step(&mut state);
+    }
+    let growth = measure(&state) as i128 - measure(&cycle_start) as i128;
+
+    let (repeats, remaining) = (
+        (n - cycle.start) / cycle.length,
+        (n - cycle.start) % cycle.length,
+    );
+    let mut state = cycle_start;
+    for _ in 0..remaining {
+        step(&mut state);
+    }
+    (measure(&state) as i128 + repeats as i128 * growth)
+        .try_into()
+        .expect("the measure after n steps is a usize")
+}
+
+#[test]
+fn methods_agree() {
+    // x -> x * x + 1 mod 255 from 3 enters a cycle after a few steps
+    let step = |x: &mut u64| *x = (*x * *x + 1) % 255;
+    let key = |x: &u64| *x;
+    let table = find_cycle(&3, step, key, CycleMethod::Table, 1000).unwrap();
+    for method in [CycleMethod::Floyd, CycleMethod::Brent] {
+        assert_eq!(find_cycle(&3, step, key, method, 1000), Some(table));
+    }
+
+    let mut seen = vec![3];
+    let mut x = 3;
+    loop {
+        step(&mut x);
+        if let Some(start) = seen.iter().position(|v| *v == x) {
+            assert_eq!(
+                table,
+                Cycle {
+                    start,
+                    length: seen.len() - start
+                }
+            );
+            break;
+        }
+        seen.push(x);
+    }
+}
+
+#[test]
+fn no_cycle() {
+    let step = |x: &mut usize| *x += 1;
+    for method in [CycleMethod::Table, CycleMethod::Floyd, CycleMethod::Brent] {
+        assert_eq!(find_cycle(&0, step, |x| *x, method, 100), None);
+        assert_eq!(extrapolate(&0, 100, step, |x| *x, |x| *x, method), 100);
+    }
+}
+
+#[test]
+fn extrapolate_shrinking() {
+    // (counter, phase) where the counter drops by 3 every cycle of 4 after 2 setup steps
+    let step = |(count, phase): &mut (usize, usize)| {
+        *count -= if *phase == 3 { 3 } else { 0 };
+        *phase = if *phase < 5 { *phase + 1 } else { 2 };
+    };
+    for method in [CycleMethod::Table, CycleMethod::Floyd, CycleMethod::Brent] {
+        let extrapolated = extrapolate(&(3000, 0), 3998, step, |v| v.1, |v| v.0, method);
+        assert_eq!(extrapolated, 3);
+    }
+}
+
+#[test]
+fn extrapolate_counter() {
+    // (counter, phase) where the counter grows by 3 every cycle of 4 after 2 setup steps
+    let step = |(count, phase): &mut (usize, usize)| {
+        *count += if *phase == 3 { 3 } else { 0 };
+        *phase = if *phase < 5 { *phase + 1 } else { 2 };
+    };
+    let direct = |n: usize| {
+        let mut state = (0, 0);
+        (0..n).for_each(|_| step(&mut state));
+        state.0
+    };
+    for method in [CycleMethod::Table, CycleMethod::Floyd, CycleMethod::Brent] {
+        for n in 0..30 {
+            let extrapolated = extrapolate(&(0, 0), n, step, |v| v.1, |v| v.0, method);
+            assert_eq!(extrapolated, direct(n));
+        }
+    }
+}
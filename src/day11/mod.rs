@@ -1,11 +1,43 @@
 extern crate test;
 
+use std::{
+    collections::BTreeMap,
+    ops::{Add, Div, Mul, Rem, Sub},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
 use itertools::Itertools;
+use num_bigint::BigInt;
 #[cfg(test)]
 use test::Bencher;
 
 use crate::utils::read_input_to_string;
 
+trait Worry:
+    Clone
+    + PartialEq
+    + From<i64>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+{
+}
+
+impl<T> Worry for T where
+    T: Clone
+        + PartialEq
+        + From<i64>
+        + Add<Output = T>
+        + Sub<Output = T>
+        + Mul<Output = T>
+        + Div<Output = T>
+        + Rem<Output = T>
+{
+}
+
 #[derive(Debug)]
 enum Operand {
     Num(i64),
@@ -13,35 +45,45 @@ enum Operand {
 }
 
 impl Operand {
-    fn value(&self, old: i64) -> i64 {
+    fn value<W: Worry>(&self, old: &W) -> W {
         match &self {
-            Self::Num(value) => *value,
-            Self::Old => old,
+            Self::Num(value) => W::from(*value),
+            Self::Old => old.clone(),
         }
     }
 }
 
-impl From<&str> for Operand {
-    fn from(s: &str) -> Self {
+impl FromStr for Operand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
         match s {
-            "old" => Operand::Old,
-            digits => Operand::Num(digits.parse().unwrap()),
+            "old" => Ok(Operand::Old),
+            digits => Ok(Operand::Num(digits.parse()?)),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 enum Operator {
     Add,
+    Sub,
     Mul,
+    Div,
+    Pow,
 }
 
-impl From<&str> for Operator {
-    fn from(s: &str) -> Self {
+impl FromStr for Operator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
         match s {
-            "+" => Operator::Add,
-            "*" => Operator::Mul,
-            _ => panic!("Unknown operator {s}"),
+            "+" => Ok(Operator::Add),
+            "-" => Ok(Operator::Sub),
+            "*" => Ok(Operator::Mul),
+            "/" => Ok(Operator::Div),
+            "^" => Ok(Operator::Pow),
+            _ => bail!("Unknown operator {s}"),
         }
     }
 }
@@ -54,24 +96,53 @@ struct Operation {
 }
 
 impl Operation {
-    fn apply(&self, old: i64) -> i64 {
+    /// Powers are reduced as they are built when `relief` works modulo something, so they
+    /// don't overflow before the relief is applied
+    fn apply<W: Worry>(&self, old: &W, relief: Relief) -> W {
         match &self.op {
             Operator::Add => self.lhs.value(old) + self.rhs.value(old),
+            Operator::Sub => self.lhs.value(old) - self.rhs.value(old),
             Operator::Mul => self.lhs.value(old) * self.rhs.value(old),
+            Operator::Div => self.lhs.value(old) / self.rhs.value(old),
+            Operator::Pow => {
+                let base = relief.reduce(self.lhs.value(old));
+                match self.rhs {
+                    Operand::Num(exp) => (0..exp).fold(relief.reduce(W::from(1)), |acc, _| {
+                        relief.reduce(acc * base.clone())
+                    }),
+                    Operand::Old => unreachable!("exponent is checked when parsing"),
+                }
+            }
         }
     }
+
+    /// Whether reducing worry levels modulo the divisors before applying this operation
+    /// gives the same divisibility results, which division does not
+    fn preserves_congruence(&self) -> bool {
+        self.op != Operator::Div
+    }
 }
 
-impl From<&str> for Operation {
-    fn from(s: &str) -> Self {
-        if let Some((lhs, op, rhs)) = s.split_whitespace().collect_tuple() {
-            Operation {
-                lhs: lhs.into(),
-                rhs: rhs.into(),
-                op: op.into(),
-            }
-        } else {
-            panic!("Unknown Operation {s}")
+impl FromStr for Operation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (lhs, op, rhs) = s
+            .split_whitespace()
+            .collect_tuple()
+            .ok_or_else(|| anyhow!("Unknown Operation {s}"))?;
+        let operation = Operation {
+            lhs: lhs.parse()?,
+            rhs: rhs.parse()?,
+            op: op.parse()?,
+        };
+        match (&operation.op, &operation.rhs) {
+            (Operator::Pow, Operand::Num(exp)) if *exp < 0 => bail!("Negative exponent in {s}"),
+            (Operator::Pow, Operand::Old) => bail!("Exponent must be a number in {s}"),
+            (Operator::Div, Operand::Num(0)) => bail!("Division by zero in {s}"),
+            // worry levels can reach 0
+            (Operator::Div, Operand::Old) => bail!("Dividing by old can divide by zero in {s}"),
+            _ => Ok(operation),
         }
     }
 }
@@ -84,8 +155,8 @@ struct Test {
 }
 
 impl Test {
-    fn apply(&self, v: i64) -> usize {
-        if v % self.divisor == 0 {
+    fn apply<W: Worry>(&self, v: &W) -> usize {
+        if v.clone() % W::from(self.divisor) == W::from(0) {
             self.monkey_true
         } else {
             self.monkey_false
@@ -93,126 +164,295 @@ impl Test {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct Item<W> {
+    id: usize,
+    worry: W,
+}
+
 #[derive(Debug)]
-struct Monkey {
-    items: Vec<i64>,
+struct Monkey<W> {
+    items: Vec<Item<W>>,
     operation: Operation,
     test: Test,
     n_inspections: usize,
 }
 
-impl Monkey {
-    fn inspect(&self, big_divis: i64, p2: bool) -> Vec<(usize, i64)> {
-        self.items
-            .iter()
-            .map(|v| {
-                let v = if p2 {
-                    self.operation.apply(*v) % big_divis
-                } else {
-                    self.operation.apply(*v) / 3
-                };
-                (self.test.apply(v), v)
+/// What happens to the worry level after an inspection
+#[derive(Debug, Clone, Copy)]
+enum Relief {
+    Divide(i64),
+    Modulo(i64),
+    Exact,
+}
+
+impl Relief {
+    fn apply<W: Worry>(&self, v: W) -> W {
+        match self {
+            Relief::Divide(divisor) => v / W::from(*divisor),
+            Relief::Modulo(_) => self.reduce(v),
+            Relief::Exact => v,
+        }
+    }
+
+    /// Keeps intermediate values small without changing the outcome, only possible modulo
+    /// something
+    fn reduce<W: Worry>(&self, v: W) -> W {
+        match self {
+            Relief::Modulo(modulus) => v % W::from(*modulus),
+            _ => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RoundStats {
+    /// Inspections per monkey so far
+    inspections: Vec<usize>,
+    /// Which monkey holds each item, indexed by item id
+    holders: Vec<usize>,
+}
+
+struct Simulation<W> {
+    monkeys: Vec<Monkey<W>>,
+    relief: Relief,
+}
+
+impl<W: Worry> Simulation<W> {
+    fn new(monkeys: Vec<Monkey<W>>, relief: Relief) -> Self {
+        Simulation { monkeys, relief }
+    }
+
+    fn round(&mut self) {
+        for i in 0..self.monkeys.len() {
+            let items = std::mem::take(&mut self.monkeys[i].items);
+            self.monkeys[i].n_inspections += items.len();
+            for Item { id, worry } in items {
+                let monkey = &self.monkeys[i];
+                let worry = self
+                    .relief
+                    .apply(monkey.operation.apply(&worry, self.relief));
+                let target = monkey.test.apply(&worry);
+                self.monkeys[target].items.push(Item { id, worry });
+            }
+        }
+    }
+
+    fn stats(&self) -> RoundStats {
+        let n_items = self.monkeys.iter().map(|v| v.items.len()).sum();
+        let mut holders = vec![0; n_items];
+        for (idx, monkey) in self.monkeys.iter().enumerate() {
+            for item in &monkey.items {
+                holders[item.id] = idx;
+            }
+        }
+        RoundStats {
+            inspections: self.monkeys.iter().map(|v| v.n_inspections).collect(),
+            holders,
+        }
+    }
+
+    /// Stats after each of the rounds, so item trajectories can be read off `holders`
+    fn run(&mut self, rounds: usize) -> Vec<RoundStats> {
+        (0..rounds)
+            .map(|_| {
+                self.round();
+                self.stats()
             })
             .collect()
     }
+
+    fn monkey_business(&self) -> usize {
+        self.monkeys
+            .iter()
+            .map(|v| v.n_inspections)
+            .sorted()
+            .rev()
+            .take(2)
+            .collect_tuple()
+            .map(|(l, r)| l * r)
+            .unwrap()
+    }
 }
 
-fn parse_starting_items(line: &str) -> Vec<i64> {
-    line.strip_prefix("Starting items: ")
-        .unwrap_or_else(|| panic!("Unexpected starting items prefix for line {line}"))
-        .split(", ")
-        .map(|v| v.parse().unwrap())
+fn parse_starting_items(value: &str) -> Result<Vec<i64>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| Ok(v.parse()?))
         .collect()
 }
 
-fn parse_operation(line: &str) -> Operation {
-    line.strip_prefix("Operation: new = ")
-        .unwrap_or_else(|| panic!("Unexpected operation prefix for line {line}"))
-        .into()
-}
-
-fn parse_test(lines: Vec<&str>) -> Test {
-    Test {
-        divisor: lines[0]
-            .strip_prefix("Test: divisible by ")
-            .unwrap_or_else(|| panic!("Unexpected test for line {}", lines[0]))
-            .parse()
-            .unwrap(),
-        monkey_true: lines[1]
-            .strip_prefix("If true: throw to monkey ")
-            .unwrap_or_else(|| panic!("Unexpected true prefix for line {}", lines[1]))
-            .parse()
-            .unwrap(),
-        monkey_false: lines[2]
-            .strip_prefix("If false: throw to monkey ")
-            .unwrap_or_else(|| panic!("Unexpected false prefix for line {}", lines[1]))
-            .parse()
-            .unwrap(),
+fn parse_number<T: FromStr>(value: &str, prefix: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(value
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("Expected {prefix:?} in {value:?}"))?
+        .trim()
+        .parse()?)
+}
+
+/// Fields after the `Monkey N:` header may come in any order
+fn parse_monkey(block: &[&str]) -> Result<(usize, Vec<i64>, Operation, Test)> {
+    let (header, fields) = block
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty monkey block"))?;
+    let id = parse_number(
+        header
+            .strip_suffix(':')
+            .ok_or_else(|| anyhow!("Unexpected header {header}"))?,
+        "Monkey",
+    )?;
+
+    let mut items = None;
+    let mut operation = None;
+    let mut divisor = None;
+    let mut monkey_true = None;
+    let mut monkey_false = None;
+    for line in fields {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Unexpected line {line} for monkey {id}"))?;
+        let value = value.trim();
+        match key.trim() {
+            "Starting items" => items = Some(parse_starting_items(value)?),
+            "Operation" => {
+                operation = Some(
+                    value
+                        .strip_prefix("new =")
+                        .ok_or_else(|| anyhow!("Unexpected operation {value}"))?
+                        .parse()?,
+                )
+            }
+            "Test" => divisor = Some(parse_number(value, "divisible by")?),
+            "If true" => monkey_true = Some(parse_number(value, "throw to monkey")?),
+            "If false" => monkey_false = Some(parse_number(value, "throw to monkey")?),
+            other => bail!("Unknown field {other} for monkey {id}"),
+        }
     }
+
+    let missing = |field| anyhow!("Missing {field} for monkey {id}");
+    let test = Test {
+        divisor: divisor.ok_or_else(|| missing("Test"))?,
+        monkey_true: monkey_true.ok_or_else(|| missing("If true"))?,
+        monkey_false: monkey_false.ok_or_else(|| missing("If false"))?,
+    };
+    ensure!(test.divisor > 0, "Divisor must be positive for monkey {id}");
+    Ok((
+        id,
+        items.ok_or_else(|| missing("Starting items"))?,
+        operation.ok_or_else(|| missing("Operation"))?,
+        test,
+    ))
 }
 
-fn parse_monkeys(input: &str) -> Vec<Monkey> {
-    input
-        .lines()
-        .map(|v| v.trim())
-        .chunks(7)
+/// Monkeys are blocks separated by blank lines and end up at the index given in their header
+fn parse_monkeys<W: Worry>(input: &str) -> Result<Vec<Monkey<W>>> {
+    let mut blocks = vec![vec![]];
+    for line in input.lines().map(str::trim) {
+        if line.is_empty() {
+            blocks.push(vec![]);
+        } else {
+            blocks.last_mut().unwrap().push(line);
+        }
+    }
+
+    let mut by_id = BTreeMap::new();
+    for block in blocks.iter().filter(|block| !block.is_empty()) {
+        let (id, items, operation, test) = parse_monkey(block)?;
+        ensure!(
+            by_id.insert(id, (items, operation, test)).is_none(),
+            "Monkey {id} is declared twice"
+        );
+    }
+    let n_monkeys = by_id.len();
+    if let Some((&id, _)) = by_id.iter().find(|(&id, _)| id >= n_monkeys) {
+        bail!("Monkey ids must be 0..{n_monkeys}, found {id}");
+    }
+
+    let mut n_items = 0;
+    by_id
         .into_iter()
-        .map(|chunk| {
-            let chunk: Vec<&str> = chunk.collect();
-            Monkey {
-                items: parse_starting_items(chunk[1]),
-                operation: parse_operation(chunk[2]),
-                test: parse_test(chunk[3..6].to_vec()),
-                n_inspections: 0,
+        .map(|(id, (items, operation, test))| {
+            for target in [test.monkey_true, test.monkey_false] {
+                ensure!(
+                    target < n_monkeys,
+                    "Monkey {id} throws to missing monkey {target}"
+                );
             }
+            let items = items
+                .into_iter()
+                .map(|worry| {
+                    n_items += 1;
+                    Item {
+                        id: n_items - 1,
+                        worry: W::from(worry),
+                    }
+                })
+                .collect();
+            Ok(Monkey {
+                items,
+                operation,
+                test,
+                n_inspections: 0,
+            })
         })
         .collect()
 }
 
-fn monkey_business(rounds: usize, mut monkeys: Vec<Monkey>, p2: bool) -> usize {
-    let big_divis_number: i64 = monkeys.iter().map(|v| v.test.divisor).product();
+fn divisor_product<W>(monkeys: &[Monkey<W>]) -> Result<i64> {
+    monkeys
+        .iter()
+        .try_fold(1i64, |acc, v| acc.checked_mul(v.test.divisor))
+        .ok_or_else(|| anyhow!("The product of the divisors does not fit in an i64"))
+}
+
+fn monkey_business<W: Worry>(rounds: usize, monkeys: Vec<Monkey<W>>, p2: bool) -> Result<usize> {
+    let relief = if p2 {
+        ensure!(
+            monkeys.iter().all(|v| v.operation.preserves_congruence()),
+            "Worry levels cannot be kept small when monkeys divide them"
+        );
+        Relief::Modulo(divisor_product(&monkeys)?)
+    } else {
+        Relief::Divide(3)
+    };
+    let mut simulation = Simulation::new(monkeys, relief);
     for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            let updates = monkeys[i].inspect(big_divis_number, p2);
-            monkeys[i].items.clear();
-            monkeys[i].n_inspections += updates.len();
-            for (monkey_idx, v) in updates {
-                monkeys[monkey_idx].items.push(v);
-            }
-        }
+        simulation.round();
     }
+    Ok(simulation.monkey_business())
+}
 
-    monkeys
-        .iter()
-        .map(|v| v.n_inspections)
-        .sorted()
-        .rev()
-        .take(2)
-        .collect_tuple()
-        .map(|(l, r)| l * r)
-        .unwrap()
+/// Part 2 rules without the modulus trick, only feasible for a small number of rounds
+#[allow(dead_code)]
+fn simulate_exact(input: &str, rounds: usize) -> Result<Vec<RoundStats>> {
+    Ok(Simulation::new(parse_monkeys::<BigInt>(input)?, Relief::Exact).run(rounds))
 }
 
-fn part1(input: &str) -> usize {
-    let monkeys = parse_monkeys(input);
+fn part1(input: &str) -> Result<usize> {
+    let monkeys = parse_monkeys::<i64>(input)?;
     monkey_business(20, monkeys, false)
 }
 
-fn part2(input: &str) -> usize {
-    let monkeys = parse_monkeys(input);
+fn part2(input: &str) -> Result<usize> {
+    let monkeys = parse_monkeys::<i64>(input)?;
     monkey_business(10_000, monkeys, true)
 }
 
-pub fn main() -> std::io::Result<()> {
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(11)?;
-    dbg!(part1(input));
-    dbg!(part2(input));
+    dbg!(part1(input)?);
+    dbg!(part2(input)?);
 
     Ok(())
 }
 
 #[test]
-fn example() {
+fn example() -> Result<()> {
     let input = "Monkey 0:
     Starting items: 79, 98
     Operation: new = old * 19
@@ -240,22 +480,210 @@ fn example() {
     Test: divisible by 17
       If true: throw to monkey 0
       If false: throw to monkey 1";
-    assert_eq!(part1(input), 10605);
-    assert_eq!(part2(input), 2713310158);
+    assert_eq!(part1(input)?, 10605);
+    assert_eq!(part2(input)?, 2713310158);
+    Ok(())
 }
 
 #[test]
-fn task() {
-    let input = &read_input_to_string(11).unwrap();
-    assert_eq!(part1(input), 54253);
-    assert_eq!(part2(input), 13119526120);
+fn example_simulation() -> Result<()> {
+    let input = "Monkey 0:
+    Starting items: 79, 98
+    Operation: new = old * 19
+    Test: divisible by 23
+      If true: throw to monkey 2
+      If false: throw to monkey 3
+  
+  Monkey 1:
+    Starting items: 54, 65, 75, 74
+    Operation: new = old + 6
+    Test: divisible by 19
+      If true: throw to monkey 2
+      If false: throw to monkey 0
+  
+  Monkey 2:
+    Starting items: 79, 60, 97
+    Operation: new = old * old
+    Test: divisible by 13
+      If true: throw to monkey 1
+      If false: throw to monkey 3
+  
+  Monkey 3:
+    Starting items: 74
+    Operation: new = old + 3
+    Test: divisible by 17
+      If true: throw to monkey 0
+      If false: throw to monkey 1";
+    let mut simulation = Simulation::new(parse_monkeys::<i64>(input)?, Relief::Divide(3));
+    simulation.round();
+    let worries = |simulation: &Simulation<i64>| {
+        simulation
+            .monkeys
+            .iter()
+            .map(|v| v.items.iter().map(|item| item.worry).collect_vec())
+            .collect_vec()
+    };
+    assert_eq!(
+        worries(&simulation),
+        vec![
+            vec![20, 23, 27, 26],
+            vec![2080, 25, 167, 207, 401, 1046],
+            vec![],
+            vec![]
+        ]
+    );
+    // the first item goes 0 -> 3 -> 1 during the first round
+    assert_eq!(simulation.stats().holders[0], 1);
+    let stats = simulation.run(19);
+    assert_eq!(stats.last().unwrap().inspections, vec![101, 95, 7, 105]);
+
+    let monkeys = parse_monkeys::<i64>(input)?;
+    let relief = Relief::Modulo(divisor_product(&monkeys)?);
+    let reduced = Simulation::new(monkeys, relief).run(20);
+    assert_eq!(reduced[0].inspections, vec![2, 4, 3, 6]);
+    assert_eq!(reduced[19].inspections, vec![99, 97, 8, 103]);
+    assert_eq!(simulate_exact(input, 20)?, reduced);
+    Ok(())
+}
+
+#[test]
+fn example_cube() -> Result<()> {
+    let input = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old ^ 3
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+    // cubing a worry level near a task-sized modulus only fits in i64 when reduced as it goes
+    let cube = "old ^ 3".parse::<Operation>()?;
+    assert_eq!(
+        cube.apply(&9_699_689i64, Relief::Modulo(9_699_690)),
+        9_699_689
+    );
+
+    let business = part2(input)?;
+    assert_eq!(
+        business,
+        monkey_business(10_000, parse_monkeys::<i128>(input)?, true)?
+    );
+
+    let monkeys = parse_monkeys::<i64>(input)?;
+    let relief = Relief::Modulo(divisor_product(&monkeys)?);
+    assert_eq!(
+        Simulation::new(monkeys, relief).run(3),
+        simulate_exact(input, 3)?
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_layout() -> Result<()> {
+    let input = "Monkey 1:
+  Operation: new = old ^ 2
+  Starting items:   7,  3
+  If false: throw to monkey 0
+  Test: divisible by 5
+  If true: throw to monkey 1
+
+
+   Monkey 0:
+Starting items:
+Test: divisible by 3
+Operation: new = old - 4
+If true: throw to monkey 1
+If false: throw to monkey 0";
+    let monkeys = parse_monkeys::<i64>(input)?;
+    assert_eq!(monkeys.len(), 2);
+    assert!(monkeys[0].items.is_empty());
+    assert_eq!(monkeys[0].operation.apply(&10i64, Relief::Exact), 6);
+    assert_eq!(
+        monkeys[1].items,
+        vec![Item { id: 0, worry: 7 }, Item { id: 1, worry: 3 }]
+    );
+    assert_eq!(monkeys[1].operation.apply(&7i64, Relief::Exact), 49);
+    assert_eq!(monkeys[1].test.apply(&49i64), 0);
+    Ok(())
+}
+
+#[test]
+fn parse_invalid() {
+    let monkey = |header: &str, target: usize, operation: &str| {
+        format!(
+            "{header}
+  Starting items: 1
+  Operation: new = {operation}
+  Test: divisible by 2
+    If true: throw to monkey {target}
+    If false: throw to monkey 0
+"
+        )
+    };
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old / 2")).is_ok());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 1, "old + 1")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 1:", 0, "old + 1")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old % 2")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old ^ old")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old / 0")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old / old")).is_err());
+    assert!(parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "6 / old")).is_err());
+    let twice = monkey("Monkey 0:", 0, "old + 1").repeat(2);
+    assert!(parse_monkeys::<i64>(&twice).is_err());
+    let divides = parse_monkeys::<i64>(&monkey("Monkey 0:", 0, "old / 2")).unwrap();
+    assert!(monkey_business(1, divides, true).is_err());
+
+    // the modulus for part 2 would overflow
+    let huge = ["Monkey 0:", "Monkey 1:", "Monkey 2:"]
+        .map(|header| monkey(header, 0, "old + 1").replace("by 2", "by 4000000000"))
+        .join("\n");
+    let huge = parse_monkeys::<i64>(&huge).unwrap();
+    assert!(divisor_product(&huge).is_err());
+    assert!(monkey_business(1, huge, true).is_err());
+}
+
+#[test]
+fn task() -> Result<()> {
+    let input = &read_input_to_string(11)?;
+    assert_eq!(part1(input)?, 54253);
+    assert_eq!(part2(input)?, 13119526120);
+    Ok(())
+}
+
+#[test]
+fn task_modulus_matches_exact() -> Result<()> {
+    let input = &read_input_to_string(11)?;
+    let monkeys = parse_monkeys::<i64>(input)?;
+    let relief = Relief::Modulo(divisor_product(&monkeys)?);
+    let reduced = Simulation::new(monkeys, relief).run(20);
+    assert_eq!(simulate_exact(input, 20)?, reduced);
+    Ok(())
 }
 
 #[bench]
 fn task_bench(b: &mut Bencher) {
     let input = &read_input_to_string(11).unwrap();
     b.iter(|| {
-        part1(input);
-        part2(input);
+        part1(input).unwrap();
+        part2(input).unwrap();
     })
 }
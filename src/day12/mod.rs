@@ -1,13 +1,18 @@
 extern crate test;
 
-use std::collections::HashSet;
+use std::time::Duration;
 
 use itertools::Itertools;
 use ndarray::Array2;
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::read_input_to_string;
+#[cfg(test)]
+use crate::export::decode_gif;
+use crate::{
+    export::{gif, Palette},
+    utils::{pretty_print, read_input_to_string},
+};
 
 fn parse_input(input: &str) -> Array2<char> {
     let board_width = input.lines().next().unwrap().len();
@@ -48,96 +53,146 @@ fn get_neighbors(
     neighbors
 }
 
-fn loop_time(arr: &Array2<char>, start: (usize, usize), end: (usize, usize)) -> Option<usize> {
-    let mut viz = arr.clone();
-
-    let mut start_visited = HashSet::new();
-    let mut start_heads = vec![start];
-
-    let mut end_visited = HashSet::new();
-    let mut end_heads = vec![end];
+/// Heightmap with `S` and `E` replaced by their heights, and how many steps every cell is
+/// from `E`
+struct Hill {
+    heights: Array2<char>,
+    start: (usize, usize),
+    end: (usize, usize),
+    /// `None` for cells that can't reach `E` at all
+    steps: Array2<Option<usize>>,
+}
 
-    let mut cnt = 0;
-    loop {
-        cnt += 1;
-        let mut next_start_heads = Vec::new();
-        for head in start_heads {
-            for (i, _) in get_neighbors(head, arr, false) {
-                if end_visited.contains(&i) {
-                    return Some(cnt);
-                }
+impl Hill {
+    /// Searches backwards from `E` once, `on_layer` sees the cells reached after each step
+    fn new(input: &str, mut on_layer: impl FnMut(&[(usize, usize)])) -> Self {
+        let mut heights = parse_input(input);
+        let (start, _) = heights.indexed_iter().find(|(_, c)| **c == 'S').unwrap();
+        let (end, _) = heights.indexed_iter().find(|(_, c)| **c == 'E').unwrap();
+        heights[start] = 'a';
+        heights[end] = 'z';
 
-                if !start_visited.contains(&i) {
-                    viz[i] = 'H';
-                    next_start_heads.push(i);
-                    start_visited.insert(i);
+        let mut steps = Array2::from_elem(heights.raw_dim(), None);
+        steps[end] = Some(0);
+        let mut layer = vec![end];
+        let mut cnt = 0;
+        while !layer.is_empty() {
+            on_layer(&layer);
+            cnt += 1;
+            let mut next_layer = Vec::new();
+            for pos in layer {
+                for (i, _) in get_neighbors(pos, &heights, true) {
+                    if steps[i].is_none() {
+                        steps[i] = Some(cnt);
+                        next_layer.push(i);
+                    }
                 }
             }
+            layer = next_layer;
         }
-        start_heads = next_start_heads;
-
-        cnt += 1;
-        let mut next_end_heads = Vec::new();
-        for head in end_heads {
-            for (i, _) in get_neighbors(head, arr, true) {
-                if start_visited.contains(&i) {
-                    return Some(cnt);
-                }
 
-                if !end_visited.contains(&i) {
-                    viz[i] = 'T';
-                    next_end_heads.push(i);
-                    end_visited.insert(i);
-                }
-            }
+        Hill {
+            heights,
+            start,
+            end,
+            steps,
+        }
+    }
+
+    /// Lowest cell closest to `E`
+    fn best_start(&self) -> Option<(usize, usize)> {
+        self.heights
+            .indexed_iter()
+            .filter(|(i, c)| **c == 'a' && self.steps[*i].is_some())
+            .min_by_key(|(i, _)| self.steps[*i])
+            .map(|(i, _)| i)
+    }
+
+    /// A shortest route from `from` to `E`, both included
+    fn route(&self, from: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let mut route = vec![from];
+        let mut pos = from;
+        let mut left = self.steps[from]?;
+        while left > 0 {
+            left -= 1;
+            (pos, _) = get_neighbors(pos, &self.heights, false)
+                .into_iter()
+                .find(|(i, _)| self.steps[*i] == Some(left))
+                .unwrap();
+            route.push(pos);
         }
-        end_heads = next_end_heads;
+        Some(route)
+    }
 
-        if start_heads.is_empty() && end_heads.is_empty() {
-            return None;
+    /// `route` drawn the way the puzzle does, with an arrow on every cell pointing at the next
+    fn draw_route(&self, route: &[(usize, usize)]) -> String {
+        let mut arr = Array2::from_elem(self.heights.raw_dim(), '.');
+        for (from, to) in route.iter().tuple_windows() {
+            arr[*from] = match (
+                to.0 as isize - from.0 as isize,
+                to.1 as isize - from.1 as isize,
+            ) {
+                (1, 0) => 'v',
+                (-1, 0) => '^',
+                (0, 1) => '>',
+                (0, -1) => '<',
+                other => panic!("Route jumps by {other:?}"),
+            };
         }
+        arr[self.end] = 'E';
+        pretty_print(&arr)
     }
 }
 
 fn part1(input: &str) -> usize {
-    let mut arr = parse_input(input);
-    let (start, _) = arr.indexed_iter().find(|(_, c)| **c == 'S').unwrap();
-    let (end, _) = arr.indexed_iter().find(|(_, c)| **c == 'E').unwrap();
-    arr[start] = 'a';
-    arr[end] = 'z';
-
-    if let Some(path_length) = loop_time(&arr, start, end) {
-        path_length
-    } else {
-        panic!("Failure");
-    }
+    let hill = Hill::new(input, |_| ());
+    hill.steps[hill.start].expect("No route from S")
 }
 
 fn part2(input: &str) -> usize {
+    let hill = Hill::new(input, |_| ());
+    hill.steps[hill.best_start().expect("No route from any a")].unwrap()
+}
+
+/// The search spreading out from `E` over the height map, ending with the route from `S`,
+/// and that route drawn as arrows
+pub fn images(input: &str) -> Vec<(&'static str, Vec<u8>)> {
     let mut arr = parse_input(input);
-    let (start, _) = arr.indexed_iter().find(|(_, c)| **c == 'S').unwrap();
-    let (end, _) = arr.indexed_iter().find(|(_, c)| **c == 'E').unwrap();
-    arr[start] = 'a';
-    arr[end] = 'z';
-
-    let starts: Vec<_> = arr
-        .indexed_iter()
-        .filter_map(|(i, c)| if *c == 'a' { Some(i) } else { None })
-        .collect();
-
-    let mut mn = arr.len();
-    for start in starts {
-        let maybe_path_length = loop_time(&arr, start, end);
-        if let Some(path_length) = maybe_path_length {
-            if path_length < mn {
-                mn = path_length;
-            }
+    arr.mapv_inplace(|c| match c {
+        'S' => 'a',
+        'E' => 'z',
+        c => c,
+    });
+    let mut frames = vec![arr.clone()];
+    let hill = Hill::new(input, |layer| {
+        for pos in layer {
+            arr[*pos] = 'T';
         }
+        frames.push(arr.clone());
+    });
+    let route = hill.route(hill.start).unwrap();
+    for pos in &route {
+        arr[*pos] = 'H';
     }
-    mn
+    frames.push(arr);
+
+    let mut colours = ('a'..='z')
+        .map(|c| {
+            let v = 40 + (c as u8 - b'a') * 7;
+            (c, [v, v, v])
+        })
+        .collect_vec();
+    colours.extend([('H', [220, 80, 60]), ('T', [60, 140, 220])]);
+    let palette = Palette::new(&colours, [0, 0, 0]);
+    vec![
+        (
+            "search.gif",
+            gif(&frames, &palette, 2, Duration::from_millis(50)),
+        ),
+        ("route.txt", (hill.draw_route(&route) + "\n").into_bytes()),
+    ]
 }
 
-#[allow(dead_code)]
 pub fn main() -> std::io::Result<()> {
     let input = &read_input_to_string(12)?;
     dbg!(part1(input));
@@ -157,11 +212,67 @@ abdefghi";
     assert_eq!(part2(input), 29);
 }
 
+#[test]
+fn example_route() {
+    let input = "Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+    let hill = Hill::new(input, |_| ());
+    let route = hill.route(hill.start).unwrap();
+    assert_eq!(route.len(), 32);
+    // as short as the puzzle's, but going down rather than right at the first tie
+    assert_eq!(
+        hill.draw_route(&route),
+        "
+v..v<<<<
+>v.vv<<^
+.v.v>E^^
+.>v>>>^^
+..>>>>>^"
+            .trim()
+    );
+
+    let best = hill.best_start().unwrap();
+    assert_eq!(hill.route(best).unwrap().len(), 30);
+    assert_eq!(hill.route(hill.end), Some(vec![hill.end]));
+
+    let images = images(input);
+    assert_eq!(images[1].0, "route.txt");
+    assert_eq!(images[1].1, (hill.draw_route(&route) + "\n").into_bytes());
+}
+
+#[test]
+fn example_images() {
+    let input = "Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+    let images = images(input);
+    assert_eq!(images[0].0, "search.gif");
+    let ((width, height), frames) = decode_gif(&images[0].1);
+    assert_eq!((width, height), (16, 10));
+    // the bare map, one frame per step of the search and the route
+    let hill = Hill::new(input, |_| ());
+    let steps = hill.steps.iter().flatten().max().unwrap();
+    assert_eq!(frames.len(), steps + 3);
+    assert!(frames[0].iter().all(|p| (1..=26).contains(p)));
+    // route cells are 'H', after the 26 heights
+    let route = frames.last().unwrap().iter().filter(|p| **p == 27).count();
+    assert_eq!(route, 32 * 4);
+}
+
 #[test]
 fn task() {
     let input = &read_input_to_string(12).unwrap();
     assert_eq!(part1(input), 352);
     assert_eq!(part2(input), 345);
+
+    let hill = Hill::new(input, |_| ());
+    assert_eq!(hill.route(hill.start).unwrap().len(), 353);
+    assert_eq!(hill.route(hill.best_start().unwrap()).unwrap().len(), 346);
 }
 
 #[bench]
@@ -1,205 +1,386 @@
 extern crate test;
 
-use std::{cmp::Ordering, collections::VecDeque};
+use std::{cmp::Ordering, fmt, slice, str::FromStr};
 
+use anyhow::{anyhow, bail, ensure, Result};
 use itertools::Itertools;
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeSeq,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 #[cfg(test)]
 use test::Bencher;
 
 use crate::utils::read_input_to_string;
+#[cfg(test)]
+use crate::utils::XorShift;
+
+#[derive(Debug, Clone)]
+enum Packet {
+    Int(u32),
+    List(Vec<Packet>),
+}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum Node {
-    List(VecDeque<Node>),
-    Leaf(u32),
+/// Compares lists item by item, the shorter one first if they agree until then
+fn cmp_lists(left: &[Packet], right: &[Packet]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .map(|(l, r)| l.cmp(r))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| left.len().cmp(&right.len()))
 }
 
-impl Ord for Node {
+impl Ord for Packet {
     fn cmp(&self, other: &Self) -> Ordering {
-        let (mut left_q, mut right_q) = match (self.to_owned(), other.to_owned()) {
-            (Node::List(left), Node::List(right)) => (left, right),
-            _ => panic!("Expected to start with both nodes being lists"),
-        };
-
-        loop {
-            let l = left_q.pop_front();
-            let r = right_q.pop_front();
-
-            let (l, r) = match (l, r) {
-                (Some(l), Some(r)) => (l, r),
-                (None, Some(_)) => return Ordering::Less,
-                (Some(_), None) => return Ordering::Greater,
-                _ => return Ordering::Equal,
-            };
-
-            match (l.clone(), r.clone()) {
-                (Node::Leaf(l), Node::Leaf(r)) => match l.cmp(&r) {
-                    Ordering::Greater => return Ordering::Greater,
-                    Ordering::Less => return Ordering::Less,
-                    Ordering::Equal => (),
-                },
-                (Node::Leaf(_), Node::List(_)) => {
-                    left_q.push_front(Node::List(VecDeque::from(vec![l])));
-                    right_q.push_front(r);
-                }
-                (Node::List(_), Node::Leaf(_)) => {
-                    left_q.push_front(l);
-                    right_q.push_front(Node::List(VecDeque::from(vec![r])));
-                }
-                (Node::List(_), Node::List(_)) => match l.cmp(&r) {
-                    Ordering::Greater => return Ordering::Greater,
-                    Ordering::Less => return Ordering::Less,
-                    Ordering::Equal => (),
-                },
-            }
+        match (self, other) {
+            (Packet::Int(l), Packet::Int(r)) => l.cmp(r),
+            (Packet::List(l), Packet::List(r)) => cmp_lists(l, r),
+            // an integer next to a list is compared as a list of just that integer
+            (Packet::Int(_), Packet::List(r)) => cmp_lists(slice::from_ref(self), r),
+            (Packet::List(l), Packet::Int(_)) => cmp_lists(l, slice::from_ref(other)),
         }
     }
 }
 
-impl PartialOrd for Node {
+impl PartialOrd for Packet {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-fn maybe_push_number(
-    mut current_number: String,
-    stack_me_some_brackets: &mut VecDeque<Node>,
-) -> String {
-    if !current_number.is_empty() {
-        match stack_me_some_brackets.back_mut() {
-            Some(Node::List(ref mut l)) => l.push_back(Node::Leaf(current_number.parse().unwrap())),
-            _ => panic!("Number with no parent to put in"),
-        }
-        current_number = String::new();
+/// Equal in the puzzle's ordering, so `[[2]]` equals `[2]` and `Ord` agrees with `==`.
+/// `to_string` tells packets apart by structure.
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
     }
-    current_number
 }
 
-fn parse_line(line: &str) -> Node {
-    let mut stack_me_some_brackets = VecDeque::new();
-    let mut current_number = String::new();
+impl Eq for Packet {}
 
-    let mut node: Option<Node> = None;
-    for c in line.chars() {
-        if let Some(prev_node) = node {
-            let current_node = stack_me_some_brackets.back_mut().unwrap();
-            match current_node {
-                Node::List(ref mut l) => l.push_back(prev_node),
-                _ => panic!("Prev node with no current node"),
-            };
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Int(v) => write!(f, "{v}"),
+            Packet::List(items) => write!(f, "[{}]", items.iter().join(",")),
         }
-        node = match c {
-            '[' => {
-                stack_me_some_brackets.push_back(Node::List(VecDeque::new()));
-                None
+    }
+}
+
+/// Parses the packet starting at `bytes[*pos]`, leaving `pos` just past it
+fn parse_packet(bytes: &[u8], pos: &mut usize) -> Result<Packet> {
+    match bytes.get(*pos) {
+        Some(b'[') => {
+            *pos += 1;
+            let mut items = Vec::new();
+            if bytes.get(*pos) == Some(&b']') {
+                *pos += 1;
+                return Ok(Packet::List(items));
             }
-            ']' => {
-                current_number = maybe_push_number(current_number, &mut stack_me_some_brackets);
-                stack_me_some_brackets.pop_back()
+            loop {
+                items.push(parse_packet(bytes, pos)?);
+                match bytes.get(*pos) {
+                    Some(b',') => *pos += 1,
+                    Some(b']') => {
+                        *pos += 1;
+                        return Ok(Packet::List(items));
+                    }
+                    Some(c) => bail!("Expected ',' or ']' at {}, got {:?}", *pos, *c as char),
+                    None => bail!("Unclosed list"),
+                }
             }
-            ',' => {
-                current_number = maybe_push_number(current_number, &mut stack_me_some_brackets);
-                None
+        }
+        Some(b'0'..=b'9') => {
+            let start = *pos;
+            while matches!(bytes.get(*pos), Some(b'0'..=b'9')) {
+                *pos += 1;
             }
-            c @ '0'..='9' => {
-                current_number.push(c);
-                None
+            let digits = std::str::from_utf8(&bytes[start..*pos])?;
+            Ok(Packet::Int(digits.parse().map_err(|e| {
+                anyhow!("Bad integer {digits} at {start}: {e}")
+            })?))
+        }
+        Some(c) => bail!("Expected '[' or a digit at {}, got {:?}", *pos, *c as char),
+        None => bail!("Unexpected end of packet"),
+    }
+}
+
+impl FromStr for Packet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut pos = 0;
+        let packet = parse_packet(s.as_bytes(), &mut pos)?;
+        ensure!(
+            pos == s.len(),
+            "Trailing input after packet: {:?}",
+            &s[pos..]
+        );
+        Ok(packet)
+    }
+}
+
+/// Packets are JSON arrays of arrays and non-negative integers
+impl Serialize for Packet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Packet::Int(v) => serializer.serialize_u32(*v),
+            Packet::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
             }
-            c => panic!("Expected bracket, comma, or number, got {c:?}"),
-        };
+        }
     }
-    node.unwrap()
 }
 
-fn part1(input: &str) -> usize {
+struct PacketVisitor;
+
+impl<'de> Visitor<'de> for PacketVisitor {
+    type Value = Packet;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list or a non-negative integer")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Packet, E> {
+        u32::try_from(v)
+            .map(Packet::Int)
+            .map_err(|_| E::custom(format!("{v} does not fit a packet integer")))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Packet, E> {
+        u64::try_from(v)
+            .map_err(|_| E::custom(format!("negative packet integer {v}")))
+            .and_then(|v| self.visit_u64(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Packet, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Packet::List(items))
+    }
+}
+
+impl<'de> Deserialize<'de> for Packet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PacketVisitor)
+    }
+}
+
+fn parse_packets(input: &str) -> Result<Vec<Packet>> {
     input
         .lines()
-        .chunks(3)
-        .into_iter()
+        .filter(|line| !line.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+fn part1(input: &str) -> Result<usize> {
+    Ok(parse_packets(input)?
+        .chunks(2)
         .enumerate()
-        .filter_map(|(i, mut v)| {
-            if parse_line(v.next().unwrap()) < parse_line(v.next().unwrap()) {
-                Some(i + 1)
-            } else {
-                None
-            }
-        })
-        .sum()
+        .filter(|(_, pair)| pair[0] < pair[1])
+        .map(|(i, _)| i + 1)
+        .sum())
 }
 
-fn part2(input: &str) -> usize {
-    let divider1 = "[[2]]";
-    let divider2 = "[[6]]";
-    let mut nodes: Vec<Node> = input
-        .lines()
-        .chain(
-            format!(
-                "{divider1}
-{divider2}"
-            )
-            .lines(),
-        )
-        .filter_map(|v| {
-            if v.is_empty() {
-                None
-            } else {
-                Some(parse_line(v))
+/// Where the divider packets end up once added to the packets and sorted
+struct Decoded {
+    /// 1-based position of each divider
+    positions: Vec<usize>,
+    /// Every packet in order, if asked for
+    listing: Option<Vec<Packet>>,
+}
+
+impl Decoded {
+    fn key(&self) -> usize {
+        self.positions.iter().product()
+    }
+}
+
+/// Positions the dividers would have in a stable sort of `packets` followed by `dividers`,
+/// found by counting what sorts before each divider rather than sorting
+fn decode(packets: &[Packet], dividers: &[Packet], with_listing: bool) -> Decoded {
+    let mut positions = vec![1; dividers.len()];
+    for packet in packets {
+        for (position, divider) in positions.iter_mut().zip(dividers) {
+            if packet <= divider {
+                *position += 1;
             }
-        })
-        .collect();
-    nodes.sort();
+        }
+    }
+    for (i, divider) in dividers.iter().enumerate() {
+        positions[i] += dividers
+            .iter()
+            .enumerate()
+            .filter(|(j, other)| *other < divider || (*other == divider && *j < i))
+            .count();
+    }
+
+    let listing = with_listing.then(|| {
+        let mut listing = packets.iter().chain(dividers).cloned().collect_vec();
+        listing.sort();
+        listing
+    });
+    Decoded { positions, listing }
+}
+
+fn dividers() -> Result<Vec<Packet>> {
+    ["[[2]]", "[[6]]"].iter().map(|s| s.parse()).collect()
+}
+
+fn part2(input: &str) -> Result<usize> {
+    Ok(decode(&parse_packets(input)?, &dividers()?, false).key())
+}
 
-    nodes
+/// Every packet and the dividers in order, one per line
+pub fn exports(input: &str) -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let decoded = decode(&parse_packets(input)?, &dividers()?, true);
+    let listing = decoded.listing.unwrap_or_default();
+    let sorted = listing
         .iter()
-        .enumerate()
-        .filter_map(|(i, v)| {
-            if v == &parse_line(divider1) || v == &parse_line(divider2) {
-                Some(i + 1)
-            } else {
-                None
-            }
-        })
-        .product()
+        .map(|packet| format!("{packet}\n"))
+        .collect::<String>();
+    Ok(vec![("sorted.txt", sorted.into_bytes())])
 }
 
-pub fn main() -> std::io::Result<()> {
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(13)?;
-    dbg!(part1(input));
-    dbg!(part2(input));
+    dbg!(part1(input)?);
+    dbg!(part2(input)?);
 
     Ok(())
 }
 
+/// Shorthand for building packets in tests
+#[cfg(test)]
+fn list<const N: usize>(items: [Packet; N]) -> Packet {
+    Packet::List(items.into())
+}
+
 #[test]
 fn test_parse1() {
     let line = "[[1,10,11],1,[1,10]]";
-    assert_eq!(
-        parse_line(line),
-        Node::List(VecDeque::from(vec![
-            Node::List(VecDeque::from(vec![
-                Node::Leaf(1),
-                Node::Leaf(10),
-                Node::Leaf(11)
-            ])),
-            Node::Leaf(1),
-            Node::List(VecDeque::from(vec![Node::Leaf(1), Node::Leaf(10)]))
-        ]))
-    )
+    let packet: Packet = line.parse().unwrap();
+    let expected = list([
+        list([Packet::Int(1), Packet::Int(10), Packet::Int(11)]),
+        Packet::Int(1),
+        list([Packet::Int(1), Packet::Int(10)]),
+    ]);
+    // `==` follows the puzzle's ordering, the text pins down the structure
+    assert_eq!(packet.to_string(), expected.to_string());
+    assert_eq!(packet.to_string(), line);
 }
 #[test]
 fn test_parse2() {
     let line = "[1,1,3,1,1]";
+    let packet: Packet = line.parse().unwrap();
+    let expected = list([
+        Packet::Int(1),
+        Packet::Int(1),
+        Packet::Int(3),
+        Packet::Int(1),
+        Packet::Int(1),
+    ]);
+    assert_eq!(packet.to_string(), expected.to_string());
+    assert_eq!(packet.to_string(), line);
+}
+
+#[test]
+fn parse_invalid() {
+    for line in [
+        "",
+        "[",
+        "[1,",
+        "[1,]",
+        "[,1]",
+        "[1 2]",
+        "[1]]",
+        "[1]x",
+        "[-1]",
+        "[a]",
+        "[99999999999]",
+    ] {
+        assert!(line.parse::<Packet>().is_err(), "{line:?} should not parse");
+    }
     assert_eq!(
-        parse_line(line),
-        Node::List(VecDeque::from(vec![
-            Node::Leaf(1),
-            Node::Leaf(1),
-            Node::Leaf(3),
-            Node::Leaf(1),
-            Node::Leaf(1),
-        ]))
-    )
+        "[[],[[]],3]".parse::<Packet>().unwrap().to_string(),
+        "[[],[[]],3]"
+    );
+}
+
+#[test]
+fn mixed_comparisons() {
+    let p = |s: &str| s.parse::<Packet>().unwrap();
+    assert_eq!(p("[[2]]"), p("[2]"));
+    assert_ne!(p("[[2]]").to_string(), p("[2]").to_string());
+    assert_eq!(p("[[[]]]").cmp(&p("[[]]")), Ordering::Greater);
+    assert!(p("[[1],[2,3,4]]") < p("[[1],4]"));
+    assert!(p("[9]") > p("[[8,7,6]]"));
+    assert!(p("[]") < p("[3]"));
+    assert_eq!(p("3").cmp(&p("[3]")), Ordering::Equal);
+}
+
+#[cfg(test)]
+fn random_packet(rng: &mut XorShift, depth: usize) -> Packet {
+    if depth == 0 || rng.below(3) == 0 {
+        Packet::Int(rng.below(4) as u32)
+    } else {
+        Packet::List(
+            (0..rng.below(4))
+                .map(|_| random_packet(rng, depth - 1))
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn fuzz_round_trip() {
+    let mut rng = XorShift::new(13);
+    for _ in 0..2000 {
+        let packet = Packet::List(vec![random_packet(&mut rng, 4)]);
+        let text = packet.to_string();
+        let parsed: Packet = text.parse().unwrap();
+        assert_eq!(parsed.to_string(), text);
+
+        // the input format is JSON
+        assert_eq!(serde_json::to_string(&packet).unwrap(), text);
+        let from_json: Packet = serde_json::from_str(&text).unwrap();
+        assert_eq!(from_json.to_string(), text);
+    }
+    assert!(serde_json::from_str::<Packet>("[1,-2]").is_err());
+    assert!(serde_json::from_str::<Packet>("[1,\"2\"]").is_err());
+    assert!(serde_json::from_str::<Packet>("[4294967296]").is_err());
+}
+
+#[test]
+fn fuzz_total_order() {
+    let mut rng = XorShift::new(2022);
+    let packets = (0..150).map(|_| random_packet(&mut rng, 3)).collect_vec();
+    for a in &packets {
+        assert_eq!(a.cmp(a), Ordering::Equal);
+        for b in &packets {
+            let ab = a.cmp(b);
+            assert_eq!(ab, b.cmp(a).reverse());
+            assert_eq!(ab.is_eq(), a == b);
+            for c in &packets {
+                if ab.is_le() && b <= c {
+                    assert!(a <= c, "{a} <= {b} <= {c}");
+                }
+            }
+        }
+    }
+
+    // sorting agrees with every pairwise comparison
+    let mut sorted = packets.clone();
+    sorted.sort();
+    assert!(sorted.windows(2).all(|w| w[0] <= w[1]));
 }
 
 #[test]
@@ -227,22 +408,121 @@ fn example() {
 
 [1,[2,[3,[4,[5,6,7]]]],8,9]
 [1,[2,[3,[4,[5,6,0]]]],8,9]";
-    assert_eq!(part1(input), 13);
-    assert_eq!(part2(input), 140);
+    assert_eq!(part1(input).unwrap(), 13);
+    assert_eq!(part2(input).unwrap(), 140);
+}
+
+#[test]
+fn example_decode() {
+    let input = "[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]";
+    let packets = parse_packets(input).unwrap();
+    let decoded = decode(&packets, &dividers().unwrap(), true);
+    assert_eq!(decoded.positions, [10, 14]);
+    let listing = decoded.listing.unwrap();
+    assert_eq!(
+        exports(input).unwrap(),
+        [(
+            "sorted.txt",
+            (listing.iter().join("\n") + "\n").into_bytes()
+        )]
+    );
+    assert_eq!(
+        listing.iter().join("\n"),
+        "[]
+[[]]
+[[[]]]
+[1,1,3,1,1]
+[1,1,5,1,1]
+[[1],[2,3,4]]
+[1,[2,[3,[4,[5,6,0]]]],8,9]
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[[1],4]
+[[2]]
+[3]
+[[4,4],4,4]
+[[4,4],4,4,4]
+[[6]]
+[7,7,7]
+[7,7,7,7]
+[[8,7,6]]
+[9]"
+    );
+
+    // dividers of our own, in any order, equal to packets or to each other
+    let dividers: Vec<Packet> = ["[9]", "[]", "[[3]]", "[1,1,3,1,1]", "[9]"]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+    let decoded = decode(&packets, &dividers, false);
+    assert_eq!(decoded.positions, [20, 2, 13, 6, 21]);
+    assert!(decoded.listing.is_none());
+}
+
+#[test]
+fn fuzz_decode() {
+    let mut rng = XorShift::new(46);
+    for _ in 0..200 {
+        let packets = (0..rng.below(20))
+            .map(|_| random_packet(&mut rng, 3))
+            .collect_vec();
+        let dividers = (0..1 + rng.below(4))
+            .map(|_| random_packet(&mut rng, 3))
+            .collect_vec();
+        let decoded = decode(&packets, &dividers, true);
+
+        // a stable sort keeps equal packets in input order, dividers after the packets
+        let mut tagged = packets
+            .iter()
+            .map(|p| (p, None))
+            .chain(dividers.iter().enumerate().map(|(i, d)| (d, Some(i))))
+            .collect_vec();
+        tagged.sort_by(|a, b| a.0.cmp(b.0));
+        for (position, (_, divider)) in tagged.iter().enumerate() {
+            if let Some(i) = divider {
+                assert_eq!(decoded.positions[*i], position + 1);
+            }
+        }
+        assert_eq!(
+            decoded.listing.unwrap().len(),
+            packets.len() + dividers.len()
+        );
+    }
 }
 
 #[test]
 fn task() {
     let input = &read_input_to_string(13).unwrap();
-    assert_eq!(part1(input), 5882);
-    assert_eq!(part2(input), 24948);
+    assert_eq!(part1(input).unwrap(), 5882);
+    assert_eq!(part2(input).unwrap(), 24948);
 }
 
 #[bench]
 fn task_bench(b: &mut Bencher) {
     b.iter(|| {
         let input = &read_input_to_string(13).unwrap();
-        part1(input);
-        part2(input);
+        part1(input).unwrap();
+        part2(input).unwrap();
     })
 }
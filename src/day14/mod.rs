@@ -1,8 +1,10 @@
 extern crate test;
 
 use std::{
+    collections::HashSet,
     iter::once,
     ops::{Add, Sub},
+    time::Duration,
 };
 
 use itertools::Itertools;
@@ -10,7 +12,13 @@ use ndarray::{s, Array2};
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::{pretty_print, read_input_to_string};
+#[cfg(test)]
+use crate::export::{decode_gif, decode_png};
+use crate::{
+    animate::{Frame, Recorder},
+    export::{gif, png, Palette},
+    utils::{pretty_print, read_input_to_string},
+};
 
 fn pretty_print_swap(arr: &Array2<char>) -> String {
     let mut arr = arr.clone();
@@ -166,17 +174,29 @@ impl Sub for Position {
 struct Cave {
     start: Position,
     map: Array2<char>,
+    /// Fall path of the previous grain, from the source down to just above where it settled
+    path: Vec<Position>,
 }
 
 impl Cave {
     fn new(input: &str, p2: bool) -> Self {
-        let path = parse_input(input);
-        let start = Position(500, 0);
+        let mut path = parse_input(input);
+        let mut start = Position(500, 0);
         let (mut mn, mut mx) = min_max_per_axis(path.iter().flatten().chain(once(&start)));
         if p2 {
-            mx = mx + Position(0, 2);
-            mx = mx + Position(mx.1 - 2, 0);
-            mn = mn - Position(mx.1 - 5, 0);
+            // sand can't spread further sideways than it falls, so the floor only needs to
+            // reach one past that on each side
+            let floor = mx.1 + 2;
+            // a floor deeper than the source is far from x = 0 lets sand spread past it, so
+            // move everything right to make room
+            let shift = Position(floor.saturating_sub(start.0), 0);
+            path.iter_mut()
+                .flatten()
+                .for_each(|pos| *pos = *pos + shift);
+            start = start + shift;
+            mn = mn + shift;
+            mn.0 = mn.0.min(start.0 - floor);
+            mx = Position((mx.0 + shift.0).max(start.0 + floor), floor);
         }
         let shape = (mx - mn) + Position(1, 1);
         let mut map = Array2::from_elem(shape.to_index(), '.');
@@ -188,7 +208,11 @@ impl Cave {
 
         let start = start - mn;
 
-        Cave { start, map }
+        Cave {
+            start,
+            map,
+            path: vec![start],
+        }
     }
 
     fn visualize(&self) -> String {
@@ -206,6 +230,44 @@ impl Cave {
 
     /// true if we landed, false otherwise
     fn drop_sand(&mut self) -> bool {
+        self.settle_next().is_some()
+    }
+
+    /// Where the next grain came to rest, `None` once it falls out or the source is covered
+    fn settle_next(&mut self) -> Option<Position> {
+        // everything on the previous grain's path above where it settled is still open, so
+        // the next grain follows the same path and can start from the last cell of it
+        let mut sand_pos = self.path.last().copied()?;
+        loop {
+            let mut next_pos = None;
+            for dir in [Direction::Down, Direction::DownLeft, Direction::DownRight] {
+                let try_pos = sand_pos.checked_add(dir)?;
+                match self.map.get(try_pos.to_index()) {
+                    Some('.') => {
+                        next_pos = Some(try_pos);
+                        break;
+                    }
+                    Some('#') | Some('o') => (),
+                    Some(c) => panic!("Unknown map character {c}"),
+                    None => return None,
+                }
+            }
+            match next_pos {
+                Some(pos) => {
+                    self.path.push(pos);
+                    sand_pos = pos;
+                }
+                None => break,
+            }
+        }
+        self.map[sand_pos.to_index()] = 'o';
+        self.path.pop();
+        Some(sand_pos)
+    }
+
+    /// Previous version of `drop_sand` that walks every grain down from the source
+    #[cfg(test)]
+    fn drop_sand_from_source(&mut self) -> bool {
         if let Some(v) = self.map.get(self.start.to_index()) {
             if *v == 'o' {
                 return false;
@@ -242,7 +304,111 @@ impl Cave {
     }
 }
 
+type Point = (i64, i64);
+
+fn rock_cells(paths: &[Vec<Position>]) -> HashSet<Point> {
+    let mut rocks = HashSet::new();
+    for path in paths {
+        for (l, r) in path.iter().tuple_windows() {
+            for x in l.0.min(r.0)..=l.0.max(r.0) {
+                for y in l.1.min(r.1)..=l.1.max(r.1) {
+                    rocks.insert((x as i64, y as i64));
+                }
+            }
+        }
+    }
+    rocks
+}
+
+/// Cave stored as the set of blocked cells, so the floor can be infinitely wide
+struct SparseCave {
+    source: Point,
+    blocked: HashSet<Point>,
+    /// Lowest rock, anything falling past it is in the abyss or lands on the floor
+    max_y: i64,
+    floor: bool,
+}
+
+impl SparseCave {
+    fn new(input: &str, floor: bool) -> Self {
+        let blocked = rock_cells(&parse_input(input));
+        let max_y = blocked.iter().map(|(_, y)| *y).max().unwrap_or(0);
+        SparseCave {
+            source: (500, 0),
+            blocked,
+            max_y,
+            floor,
+        }
+    }
+
+    fn is_blocked(&self, point: Point) -> bool {
+        (self.floor && point.1 == self.max_y + 2) || self.blocked.contains(&point)
+    }
+
+    /// Where the next grain comes to rest, `None` once it falls into the abyss or the
+    /// source is covered
+    fn drop_sand(&mut self) -> Option<Point> {
+        if self.is_blocked(self.source) {
+            return None;
+        }
+        let (mut x, mut y) = self.source;
+        'falling: loop {
+            if y > self.max_y + 1 {
+                return None;
+            }
+            for dx in [0, -1, 1] {
+                if !self.is_blocked((x + dx, y + 1)) {
+                    (x, y) = (x + dx, y + 1);
+                    continue 'falling;
+                }
+            }
+            break;
+        }
+        self.blocked.insert((x, y));
+        Some((x, y))
+    }
+
+    /// Resting place of every grain, in the order they were dropped
+    fn fill(&mut self) -> Vec<Point> {
+        std::iter::from_fn(|| self.drop_sand()).collect()
+    }
+}
+
+/// Part 2 without simulating grains: with a floor, sand ends up in exactly the cells that
+/// can be reached from the source by falling straight or diagonally, so fill row by row
+fn count_floor_fill(input: &str) -> usize {
+    let rocks = rock_cells(&parse_input(input));
+    let floor = rocks.iter().map(|(_, y)| *y).max().unwrap_or(0) + 2;
+    let source_x = 500;
+
+    // row[i] is x = source_x - y + i
+    let mut row = vec![true];
+    let mut count = 1;
+    for y in 1..floor {
+        let next = (0..2 * y as usize + 1)
+            .map(|i| {
+                let x = source_x - y + i as i64;
+                !rocks.contains(&(x, y))
+                    && (i.saturating_sub(2)..i.min(row.len() - 1) + 1).any(|j| row[j])
+            })
+            .collect_vec();
+        count += next.iter().filter(|v| **v).count();
+        row = next;
+    }
+    count
+}
+
+/// Largest rock bounding box, in cells, that part 1 simulates on a dense map
+const DENSE_LIMIT: usize = 1 << 24;
+
 fn part1(input: &str) -> usize {
+    let paths = parse_input(input);
+    let (mn, mx) = min_max_per_axis(paths.iter().flatten().chain(once(&Position(500, 0))));
+    if (mx.0 - mn.0 + 1) * (mx.1 - mn.1 + 1) > DENSE_LIMIT {
+        // rocks far apart, only store the cells that are actually blocked
+        return SparseCave::new(input, false).fill().len();
+    }
+
     let mut cave = Cave::new(input, false);
     let mut i = 0;
     while cave.drop_sand() {
@@ -252,12 +418,49 @@ fn part1(input: &str) -> usize {
 }
 
 fn part2(input: &str) -> usize {
+    count_floor_fill(input)
+}
+
+/// Part 2 filling up, following where the grains land
+pub fn animate(input: &str) -> Vec<Frame> {
     let mut cave = Cave::new(input, true);
-    let mut i = 0;
-    while cave.drop_sand() {
-        i += 1;
+    let mut recorder = Recorder::with_stride(50);
+    while let Some(pos) = cave.settle_next() {
+        recorder.record(|| Frame::focused(cave.visualize(), (pos.1, pos.0)));
     }
-    i
+    recorder.record_final(|| Frame::new(cave.visualize()));
+    recorder.into_frames()
+}
+
+/// Part 1 filling up ten grains at a time, and the pile part 2 ends with
+pub fn images(input: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let palette = Palette::new(
+        &[('#', [110, 100, 90]), ('o', [230, 190, 90])],
+        [20, 20, 30],
+    );
+
+    let mut cave = Cave::new(input, false);
+    let mut frames = vec![cave.map.t().to_owned()];
+    for i in 1.. {
+        let landed = cave.drop_sand();
+        if i % 10 == 0 || !landed {
+            frames.push(cave.map.t().to_owned());
+        }
+        if !landed {
+            break;
+        }
+    }
+
+    let mut floor = Cave::new(input, true);
+    while floor.drop_sand() {}
+
+    vec![
+        (
+            "sand.gif",
+            gif(&frames, &palette, 2, Duration::from_millis(40)),
+        ),
+        ("floor.png", png(&floor.map.t().to_owned(), &palette, 2)),
+    ]
 }
 
 pub fn main() -> std::io::Result<()> {
@@ -338,23 +541,138 @@ fn example_visualize_drop_p2() {
     assert_eq!(
         cave.visualize(),
         "
-............o............
-...........ooo...........
-..........ooooo..........
-.........ooooooo.........
-........oo#ooo##o........
-.......ooo#ooo#ooo.......
-......oo###ooo#oooo......
-.....oooo.oooo#ooooo.....
-....oooooooooo#oooooo....
-...ooo#########ooooooo...
-..ooooo.......ooooooooo..
-#########################
+...........o...........
+..........ooo..........
+.........ooooo.........
+........ooooooo........
+.......oo#ooo##o.......
+......ooo#ooo#ooo......
+.....oo###ooo#oooo.....
+....oooo.oooo#ooooo....
+...oooooooooo#oooooo...
+..ooo#########ooooooo..
+.ooooo.......ooooooooo.
+#######################
 "
         .trim()
     );
 }
 
+#[test]
+fn example_sparse() {
+    let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+    let mut cave = SparseCave::new(input, false);
+    let grains = cave.fill();
+    assert_eq!(grains.len(), 24);
+    assert_eq!(
+        grains[..5],
+        [(500, 8), (499, 8), (501, 8), (500, 7), (498, 8)]
+    );
+    assert_eq!(grains[23], (495, 8));
+
+    let mut cave = SparseCave::new(input, true);
+    let grains = cave.fill();
+    assert_eq!(grains.len(), 93);
+    assert_eq!(grains.last(), Some(&(500, 0)));
+    assert_eq!(count_floor_fill(input), 93);
+
+    let mut dense = Cave::new(input, true);
+    while dense.drop_sand() {}
+    let dense_grains: HashSet<Point> = dense
+        .map
+        .indexed_iter()
+        .filter(|(_, c)| **c == 'o')
+        .map(|((x, y), _)| ((x + 500 - dense.start.0) as i64, y as i64))
+        .collect();
+    assert_eq!(dense_grains, grains.into_iter().collect());
+}
+
+#[test]
+fn floor_wider_than_source() {
+    // a floor far below a single rock needs sand to spread to x < 0
+    let input = "500,600 -> 500,600";
+    let mut cave = SparseCave::new(input, true);
+    let grains = cave.fill();
+    assert!(grains.iter().any(|(x, _)| *x < 0));
+    assert_eq!(grains.len(), count_floor_fill(input));
+}
+
+#[test]
+fn deep_floor() {
+    // the floor is further below the source than the source is from x = 0
+    let input = "500,600 -> 500,600";
+    let mut cave = Cave::new(input, true);
+    let mut grains = 0;
+    while cave.drop_sand() {
+        grains += 1;
+    }
+    assert_eq!(grains, count_floor_fill(input));
+    assert_eq!(cave.map.dim(), (2 * 602 + 1, 603));
+}
+
+#[test]
+fn spread_out_rocks() {
+    // a single far away rock makes the bounding box too big for a dense map
+    let shelf = "499,2 -> 501,2";
+    let input = &format!("{shelf}\n20000,20000 -> 20000,20000");
+    assert_eq!(part1(shelf), 1);
+    assert_eq!(part1(input), 1);
+}
+
+#[test]
+fn example_resumed_drop() {
+    let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+    for p2 in [false, true] {
+        let mut cave = Cave::new(input, p2);
+        let mut expected = Cave::new(input, p2);
+        loop {
+            let landed = cave.drop_sand();
+            assert_eq!(landed, expected.drop_sand_from_source());
+            assert_eq!(cave.map, expected.map);
+            if !landed {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn example_animate() {
+    let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+    let frames = animate(input);
+    // grains 1, 51 and the last of 93
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].focus, Some((8, 11)));
+    let mut cave = Cave::new(input, true);
+    while cave.drop_sand() {}
+    assert_eq!(frames[2].text, cave.visualize());
+}
+
+#[test]
+fn example_images() {
+    let input = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+    let images = images(input);
+    assert_eq!(images[0].0, "sand.gif");
+    let ((width, height), frames) = decode_gif(&images[0].1);
+    assert_eq!((width, height), (20, 20));
+    // empty, after 10 and 20 grains, and once the 25th falls out
+    assert_eq!(frames.len(), 4);
+    let sand = |pixels: &[u8]| pixels.iter().filter(|p| **p == 2).count() / 4;
+    assert_eq!(
+        frames.iter().map(|f| sand(f)).collect_vec(),
+        [0, 10, 20, 24]
+    );
+
+    assert_eq!(images[1].0, "floor.png");
+    let ((width, height), pixels) = decode_png(&images[1].1);
+    assert_eq!((width, height), (46, 24));
+    assert_eq!(sand(&pixels), 93);
+}
+
 #[test]
 fn example() {
     let input = "498,4 -> 498,6 -> 496,6
@@ -368,6 +686,27 @@ fn task() {
     let input = &read_input_to_string(14).unwrap();
     assert_eq!(part1(input), 674);
     assert_eq!(part2(input), 24958);
+    assert_eq!(SparseCave::new(input, false).fill().len(), 674);
+    assert_eq!(SparseCave::new(input, true).fill().len(), 24958);
+}
+
+#[test]
+fn task_resumed_drop() {
+    let input = &read_input_to_string(14).unwrap();
+    for p2 in [false, true] {
+        let mut cave = Cave::new(input, p2);
+        let mut expected = Cave::new(input, p2);
+        for i in 0.. {
+            let landed = cave.drop_sand();
+            assert_eq!(landed, expected.drop_sand_from_source());
+            if i % 1000 == 0 || !landed {
+                assert_eq!(cave.map, expected.map);
+            }
+            if !landed {
+                break;
+            }
+        }
+    }
 }
 
 #[bench]
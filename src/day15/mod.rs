@@ -1,364 +1,96 @@
 extern crate test;
 
-use std::{
-    cmp::Ordering,
-    isize,
-    ops::{Add, Range, Sub},
-};
-
 use itertools::Itertools;
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::read_input_to_string;
+use crate::{
+    coverage::{
+        coverage_area, excluded_in_row, row_intervals, uncovered, Diamond, Interval, Point,
+    },
+    utils::read_input_to_string,
+};
 
 const P1_LOC: usize = 2_000_000;
 const P2_MAX: usize = 4_000_000;
 
+fn parse_point(s: &str) -> Point {
+    let (x, y) = s
+        .split_once(", ")
+        .unwrap_or_else(|| panic!("Unexpected string for Point {s}"));
+    (
+        x.parse().unwrap_or_else(|_| panic!("bad digit {x}")),
+        y.parse().unwrap_or_else(|_| panic!("bad digit {y}")),
+    )
+}
+
 fn parse_line(line: &str) -> (Point, Point) {
     let line = line.replace("x=", "").replace("y=", "");
     let (sensor, beacon) = line.split_once(": ").unwrap();
     (
-        sensor.strip_prefix("Sensor at ").unwrap().into(),
-        beacon.strip_prefix("closest beacon is at ").unwrap().into(),
+        parse_point(sensor.strip_prefix("Sensor at ").unwrap()),
+        parse_point(beacon.strip_prefix("closest beacon is at ").unwrap()),
     )
 }
 
-fn to_unit(v: isize) -> isize {
-    match v.cmp(&0) {
-        Ordering::Less => -1,
-        Ordering::Greater => 1,
-        Ordering::Equal => 0,
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Point(isize, isize);
-
-impl Point {
-    fn to_unit(self) -> Self {
-        Self(to_unit(self.0), to_unit(self.1))
-    }
-
-    fn manhattan(&self, other: &Point) -> usize {
-        self.0.abs_diff(other.0) + self.1.abs_diff(other.1)
-    }
-}
-
-impl From<&str> for Point {
-    fn from(s: &str) -> Self {
-        let (x, y) = s
-            .split_once(", ")
-            .unwrap_or_else(|| panic!("Unexpected string for Point {s}"));
-        Point(
-            x.parse().unwrap_or_else(|_| panic!("bad digit {x}")),
-            y.parse().unwrap_or_else(|_| panic!("bad digit {y}")),
-        )
-    }
-}
-
-impl Add for Point {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self::Output {
-        Self(self.0 + other.0, self.1 + other.1)
-    }
-}
-
-impl Sub for Point {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self::Output {
-        Self(
-            self.0.checked_sub(other.0).unwrap(),
-            self.1.checked_sub(other.1).unwrap(),
-        )
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Line {
-    m: isize,
-    b: isize,
-    min_x: isize,
-    max_x: isize,
-}
-
-impl Line {
-    fn is_on_side_inclusive(&self, point: Point, above: bool) -> bool {
-        if above {
-            point.1 <= self.m * point.0 + self.b
-        } else {
-            point.1 >= self.m * point.0 + self.b
-        }
-    }
-
-    fn from_start_end(start: Point, end: Point) -> Self {
-        let m = (end - start).to_unit().1;
-        if m == 0 {
-            panic!("got bad m for start {start:?} end {end:?}");
-        }
-        Line {
-            m,
-            b: start.1 - (m * start.0),
-            min_x: start.0.min(end.0),
-            max_x: start.0.max(end.0),
-        }
-    }
-
-    fn y_at(&self, x: isize) -> Point {
-        Point(x, self.m * x + self.b)
-    }
-
-    fn x_at(&self, y: isize) -> Point {
-        Point((y - self.b) / self.m, y)
-    }
-
-    fn walk_in_range(self, xy_range: Range<isize>) -> impl Iterator<Item = Point> {
-        let valid_range = if self.m < 0 {
-            *[self.x_at(xy_range.end - 1).0, xy_range.start, self.min_x]
-                .iter()
-                .max()
-                .unwrap()
-                ..*[xy_range.end, self.max_x, self.x_at(xy_range.start).0]
-                    .iter()
-                    .min()
-                    .unwrap()
-        } else {
-            *[self.x_at(xy_range.start).0, xy_range.start, self.min_x]
-                .iter()
-                .max()
-                .unwrap()
-                ..*[self.x_at(xy_range.end - 1).0, xy_range.end, self.max_x]
-                    .iter()
-                    .min()
-                    .unwrap()
-        };
-
-        valid_range.into_iter().map(move |x| self.y_at(x))
-    }
-
-    fn intersect(&self, other: &Line) -> Option<Point> {
-        if self.m == other.m {
-            return None;
-        }
-
-        let y = (self.m * ((other.b - self.b) / (self.m - other.m))) + self.b;
-        Some(if self.m != 0 {
-            self.x_at(y)
-        } else {
-            other.x_at(y)
-        })
-    }
-}
-
-#[derive(Debug)]
-struct Diamond {
-    top_right: Line,
-    left_top: Line,
-    left_bottom: Line,
-    bottom_right: Line,
-}
-
-impl Diamond {
-    fn from_center_radius(center: Point, radius: usize) -> Self {
-        if radius == 0 {
-            return Self {
-                top_right: Line {
-                    m: 1,
-                    b: center.1,
-                    min_x: center.0,
-                    max_x: center.0,
-                },
-                left_top: Line {
-                    m: 1,
-                    b: center.1,
-                    min_x: center.0,
-                    max_x: center.0,
-                },
-                left_bottom: Line {
-                    m: 1,
-                    b: center.1,
-                    min_x: center.0,
-                    max_x: center.0,
-                },
-                bottom_right: Line {
-                    m: 1,
-                    b: center.1,
-                    min_x: center.0,
-                    max_x: center.0,
-                },
-            };
-        }
-
-        let top = center - Point(0, radius as isize);
-        let left = center - Point(radius as isize, 0);
-        let bottom = center + Point(0, radius as isize);
-        let right = center + Point(radius as isize, 0);
-
-        Self {
-            left_top: Line::from_start_end(left, top),
-            top_right: Line::from_start_end(top, right),
-            left_bottom: Line::from_start_end(left, bottom),
-            bottom_right: Line::from_start_end(bottom, right),
-        }
-    }
-
-    fn is_inside(&self, point: Point) -> bool {
-        self.top_right.is_on_side_inclusive(point, false)
-            && self.left_top.is_on_side_inclusive(point, false)
-            && self.left_bottom.is_on_side_inclusive(point, true)
-            && self.bottom_right.is_on_side_inclusive(point, true)
-    }
-
-    fn walk_outer(self) -> impl Iterator<Item = Line> {
-        [
-            self.top_right,
-            self.left_top,
-            self.left_bottom,
-            self.bottom_right,
-        ]
-        .into_iter()
-    }
-}
-
-fn range_at_distance(x: isize, radius: usize, distance: usize) -> Option<(isize, isize)> {
-    if distance > radius {
-        return None;
-    }
-
-    let delta = radius.checked_sub(distance).unwrap();
-    Some((x - delta as isize, x + delta as isize))
-}
-
-fn merge_ranges(mut ranges: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
-    ranges.sort_by(|a, b| a.0.cmp(&b.0));
-    ranges
-        .into_iter()
-        .fold(Vec::new(), |mut acc: Vec<(isize, isize)>, range| {
-            if let Some(last_merged) = acc.last_mut() {
-                if last_merged.1 >= (range.0 - 1) {
-                    last_merged.1 = range.1.max(last_merged.1);
-                } else {
-                    acc.push(range);
-                }
-            } else {
-                acc.push(range);
-            }
-            acc
-        })
-}
-
-fn count_points_in_ranges(ranges: Vec<(isize, isize)>) -> isize {
-    ranges.iter().map(|(l, r)| *r - *l).sum()
-}
-
-fn part1(input: &str, y: usize) -> usize {
-    let mut ranges: Vec<_> = input
-        .lines()
-        .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .filter_map(|(sensor, radius)| {
-            range_at_distance(sensor.0, radius, (sensor.1 - y as isize).unsigned_abs())
-        })
-        .collect();
-    ranges = merge_ranges(ranges);
-
-    count_points_in_ranges(ranges) as usize
-}
-
-fn part2(input: &str, mx: usize) -> usize {
-    let diamonds: Vec<_> = input
-        .lines()
-        .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .map(|(sensor, radius)| Diamond::from_center_radius(sensor, radius))
-        .collect();
-
-    let outer_lines: Vec<_> = input
+fn parse(input: &str) -> (Vec<Diamond>, Vec<Point>) {
+    input
         .lines()
         .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .map(|(sensor, radius)| Diamond::from_center_radius(sensor, radius + 1))
-        .flat_map(|diamond| diamond.walk_outer())
-        .collect();
-
-    let check_range = 0..(mx + 1) as isize;
-
-    for pt in outer_lines.iter().permutations(2).filter_map(|v| {
-        if let Some(pt) = v[0].intersect(v[1]) {
-            if check_range.contains(&pt.0) && check_range.contains(&pt.1) {
-                return Some(pt);
-            }
-        }
-        None
-    }) {
-        if diamonds.iter().all(|diamond| !diamond.is_inside(pt)) {
-            return pt.0 as usize * P2_MAX + pt.1 as usize;
-        }
-    }
-
-    panic!("No solution found")
-}
-
-#[allow(dead_code)]
-fn part2_2(input: &str, mx: usize) -> usize {
-    let sensor_radiuses: Vec<_> = input
-        .lines()
-        .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .collect_vec();
-
-    for y in 0..mx as isize {
-        let mut ranges = sensor_radiuses
+        .map(|(sensor, beacon)| (Diamond::reaching(sensor, beacon), beacon))
+        .unzip()
+}
+
+/// Everything known about a single row
+#[derive(Debug, PartialEq, Eq)]
+struct Row {
+    /// Disjoint, sorted x ranges seen by some sensor
+    intervals: Vec<Interval>,
+    /// Cells inside `intervals`
+    covered: usize,
+    /// Covered cells that cannot hold a beacon we don't know about yet
+    excluded: usize,
+    /// Distinct known beacons on the row, all of them covered
+    beacons: usize,
+    /// Sensors on the row, all of them covered
+    sensors: usize,
+}
+
+fn row(diamonds: &[Diamond], beacons: &[Point], y: i64) -> Row {
+    let intervals = row_intervals(diamonds, y);
+    let on_row =
+        |points: &mut dyn Iterator<Item = &Point>| points.filter(|p| p.1 == y).unique().count();
+    Row {
+        covered: intervals
             .iter()
-            .filter_map(|(sensor, radius)| {
-                range_at_distance(sensor.0, *radius, (sensor.1 - y).unsigned_abs())
-            })
-            .collect();
-
-        ranges = merge_ranges(ranges);
-        if ranges.len() > 1 {
-            dbg!(ranges.clone(), y);
-            return (ranges[0].1 + 1) as usize * P2_MAX + y as usize;
-        }
+            .map(|(lo, hi)| (hi - lo + 1) as usize)
+            .sum(),
+        excluded: excluded_in_row(diamonds, beacons, y) as usize,
+        beacons: on_row(&mut beacons.iter()),
+        sensors: on_row(&mut diamonds.iter().map(|d| &d.center)),
+        intervals,
     }
-    panic!("No solution found");
 }
 
-#[allow(dead_code)]
-fn part2_3(input: &str, mx: usize) -> usize {
-    let diamonds: Vec<_> = input
-        .lines()
-        .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .map(|(sensor, radius)| Diamond::from_center_radius(sensor, radius))
-        .collect();
-
-    let diamond_outers: Vec<_> = input
-        .lines()
-        .map(parse_line)
-        .map(|(sensor, beacon)| (sensor, sensor.manhattan(&beacon)))
-        .map(|(sensor, radius)| Diamond::from_center_radius(sensor, radius + 1))
-        .collect();
+fn part1(input: &str, y: usize) -> usize {
+    let (diamonds, beacons) = parse(input);
+    row(&diamonds, &beacons, y as i64).excluded
+}
 
-    for pt in diamond_outers.into_iter().flat_map(|diamond| {
-        diamond
-            .walk_outer()
-            .flat_map(|line| line.walk_in_range(0..mx as isize + 1))
-    }) {
-        if diamonds.iter().all(|diamond| !diamond.is_inside(pt)) {
-            dbg!(pt);
-            return pt.0 as usize * P2_MAX + pt.1 as usize;
-        }
+fn part2(input: &str, mx: usize) -> usize {
+    let (diamonds, _) = parse(input);
+    match uncovered(&diamonds, (0, 0), (mx as i64, mx as i64))[..] {
+        [(x, y)] => x as usize * P2_MAX + y as usize,
+        ref points => panic!("Expected a single free spot, found {}", points.len()),
     }
-    panic!("No solution found")
 }
 
 pub fn main() -> std::io::Result<()> {
     let input = &read_input_to_string(15)?;
     dbg!(part1(input, P1_LOC));
     dbg!(part2(input, P2_MAX));
+    // cells seen by at least one sensor, over the whole plane
+    dbg!(coverage_area(&parse(input).0));
 
     Ok(())
 }
@@ -380,41 +112,54 @@ Sensor at x=16, y=7: closest beacon is at x=15, y=3
 Sensor at x=14, y=3: closest beacon is at x=15, y=3
 Sensor at x=20, y=1: closest beacon is at x=15, y=3";
     assert_eq!(part1(input, 10), 26);
+    let (diamonds, beacons) = parse(input);
+    let example_row = row(&diamonds, &beacons, 16);
+    assert_eq!(example_row.beacons, 1);
+    assert_eq!(example_row.sensors, 1);
     assert_eq!(part2(input, 20), 56000011);
-    assert_eq!(part2_2(input, 20), 56000011);
-    assert_eq!(part2(input, 20), 56000011);
+}
+
+#[test]
+fn rows() {
+    let (diamonds, beacons) = parse(
+        "Sensor at x=0, y=0: closest beacon is at x=2, y=0
+Sensor at x=10, y=0: closest beacon is at x=8, y=0
+Sensor at x=5, y=3: closest beacon is at x=5, y=0
+Sensor at x=6, y=3: closest beacon is at x=5, y=0",
+    );
+    let check = |y, intervals: &[Interval], covered, beacons_on_row, sensors| {
+        assert_eq!(
+            row(&diamonds, &beacons, y),
+            Row {
+                intervals: intervals.to_vec(),
+                covered,
+                excluded: covered - beacons_on_row,
+                beacons: beacons_on_row,
+                sensors,
+            },
+            "row {y}"
+        );
+    };
+    check(0, &[(-2, 2), (5, 12)], 13, 3, 2);
+    check(1, &[(-1, 1), (4, 11)], 11, 0, 0);
+    check(3, &[(2, 10)], 9, 0, 2);
+    check(-2, &[(0, 0), (10, 10)], 2, 0, 0);
+    check(-3, &[], 0, 0, 0);
+    check(100, &[], 0, 0, 0);
 }
 
 #[test]
 fn task() {
     let input = &read_input_to_string(15).unwrap();
     assert_eq!(part1(input, P1_LOC), 4919281);
-    // assert_eq!(part2(input, P2_MAX), 12630143363767);
-    // assert_eq!(part2_2(input, P2_MAX), 12630143363767);
     assert_eq!(part2(input, P2_MAX), 12630143363767);
 }
 
-// #[bench]
-// fn task_bench(b: &mut Bencher) {
-//     let input = &read_input_to_string(15).unwrap();
-//     b.iter(|| {
-//         part2_3(input, P2_MAX);
-//     })
-// }
-
-// #[bench]
-// fn task_bench_2(b: &mut Bencher) {
-//     let input = &read_input_to_string(15).unwrap();
-//     b.iter(|| {
-//         part2_2(input, P2_MAX);
-//     })
-// }
-
 #[bench]
-fn task_bench_3(b: &mut Bencher) {
+fn task_bench(b: &mut Bencher) {
     let input = &read_input_to_string(15).unwrap();
     b.iter(|| {
         part1(input, P1_LOC);
-        // part2(input, P2_MAX);
+        part2(input, P2_MAX);
     })
 }
@@ -1,16 +1,24 @@
 extern crate test;
 
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
-    ops::Sub,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    iter::once,
 };
 
 use itertools::Itertools;
-use petgraph::{algo::floyd_warshall, prelude::NodeIndex, prelude::UnGraph, visit::Bfs};
+use petgraph::{
+    algo::{astar, dijkstra},
+    prelude::NodeIndex,
+    prelude::UnGraph,
+    visit::EdgeRef,
+};
 #[cfg(test)]
 use test::Bencher;
 
 use crate::utils::read_input_to_string;
+#[cfg(test)]
+use crate::utils::XorShift;
 
 fn parse_node(line: &str) -> (&str, usize, Vec<&str>) {
     let (room, connections) = line
@@ -40,125 +48,338 @@ fn parse_nodes(input: &str) -> Cave {
             cave.update_edge(name_map[name], name_map[other], 1usize);
         }
     }
-    // println!("{:?}", Dot::new(&cave));
-
     cave
 }
 
+/// Steps between every pair of `nodes` through the whole cave, `None` if there is no way
+fn distance_matrix(cave: &Cave, nodes: &[NodeIndex]) -> Vec<Vec<Option<usize>>> {
+    nodes
+        .iter()
+        .map(|from| {
+            let dist = dijkstra(cave, *from, None, |edge| *edge.weight());
+            nodes.iter().map(|to| dist.get(to).copied()).collect()
+        })
+        .collect()
+}
+
+/// Only the start and the valves worth opening, with a tunnel between two of them unless
+/// the shortest way already passes another one of them
 fn prune_nodes(start: NodeIndex, cave: &Cave) -> Cave {
-    // Could've just done this in the shortest paths step
-    let mut cave = cave.clone();
-    let mut bfs = Bfs::new(&cave, start);
-    while let Some(nx) = bfs.next(&cave) {
-        if cave[nx].0 == 0 && cave[nx].1 != "AA" {
-            for pair in cave.clone().neighbors(nx).permutations(2) {
-                let dist = cave[cave.find_edge(pair[0], nx).unwrap()]
-                    + cave[cave.find_edge(pair[1], nx).unwrap()];
-                if let Some(edge) = cave.find_edge(pair[0], pair[1]) {
-                    if dist < cave[edge] {
-                        cave[edge] = dist;
-                    }
-                } else {
-                    cave.add_edge(pair[0], pair[1], dist);
-                }
-            }
+    let nodes = cave
+        .node_indices()
+        .filter(|nx| *nx == start || cave[*nx].0 > 0)
+        .collect_vec();
+    let dist = distance_matrix(cave, &nodes);
+
+    let mut pruned = Cave::default();
+    let idx = nodes
+        .iter()
+        .map(|nx| pruned.add_node(cave[*nx].clone()))
+        .collect_vec();
+    for (a, b) in (0..nodes.len()).tuple_combinations() {
+        let Some(d) = dist[a][b] else {
+            continue;
+        };
+        let via_other = (0..nodes.len()).any(|c| {
+            c != a && c != b && matches!((dist[a][c], dist[c][b]), (Some(x), Some(y)) if x + y == d)
+        });
+        if !via_other {
+            pruned.add_edge(idx[a], idx[b], d);
         }
     }
-
-    cave.retain_nodes(|graph, nx| graph[nx].0 != 0 || graph[nx].1 == "AA");
-    cave
+    pruned
 }
 
-fn visit(
-    position: &NodeIndex,
-    remaining: usize,
-    released: usize,
-    closed_valves: &BTreeSet<NodeIndex>,
-    flow: usize,
-    cave: &Cave,
-    distance_map: &HashMap<(NodeIndex, NodeIndex), usize>,
-) -> usize {
-    if closed_valves.is_empty() || remaining == 0 {
-        return released + (flow * remaining);
-    }
-
-    closed_valves
-        .clone()
+/// Graphviz source for `cave` with flow rates on the valves and distances on the tunnels.
+/// The shortest walk through the valves named in `route` is highlighted
+fn to_dot(cave: &Cave, route: &[&str]) -> String {
+    let find = |name: &str| {
+        cave.node_indices()
+            .find(|nx| cave[*nx].1 == name)
+            .unwrap_or_else(|| panic!("No valve {name}"))
+    };
+    let walk = route
         .iter()
-        .map(|candidate| {
-            let cost = distance_map[&(*position, *candidate)] + 1;
-            if remaining < cost {
-                released + (flow * remaining)
+        .map(|name| find(name))
+        .tuple_windows()
+        .flat_map(|(from, to)| {
+            let (_, path) = astar(cave, from, |nx| nx == to, |edge| *edge.weight(), |_| 0).unwrap();
+            path.into_iter().tuple_windows()
+        })
+        .collect_vec();
+    let on_route: HashSet<NodeIndex> = route
+        .iter()
+        .map(|name| find(name))
+        .chain(walk.iter().flat_map(|(a, b)| [*a, *b]))
+        .collect();
+    const HIGHLIGHT: &str = ", color=red, penwidth=2";
+
+    // sorted by name, so the output doesn't depend on how the graph was built
+    let mut out = String::from("graph {\n");
+    for nx in cave.node_indices().sorted_by_key(|nx| &cave[*nx].1) {
+        let (flow, name) = &cave[nx];
+        let style = if on_route.contains(&nx) {
+            HIGHLIGHT
+        } else {
+            ""
+        };
+        writeln!(out, "    {name} [label=\"{name}\\n{flow}\"{style}];").unwrap();
+    }
+    let edges = cave
+        .edge_references()
+        .map(|edge| {
+            let (a, b) = (edge.source(), edge.target());
+            let (a, b) = if cave[a].1 <= cave[b].1 {
+                (a, b)
             } else {
-                let mut closed_valves = closed_valves.clone();
-                closed_valves.remove(candidate);
-                visit(
-                    candidate,
-                    remaining - cost,
-                    released + (cost * flow),
-                    &closed_valves,
-                    flow + cave[*candidate].0,
-                    cave,
-                    distance_map,
-                )
-            }
+                (b, a)
+            };
+            (
+                &cave[a].1,
+                &cave[b].1,
+                edge.weight(),
+                walk.contains(&(a, b)) || walk.contains(&(b, a)),
+            )
         })
-        .max()
-        .unwrap()
+        .sorted();
+    for (a, b, weight, highlight) in edges {
+        let style = if highlight { HIGHLIGHT } else { "" };
+        writeln!(out, "    {a} -- {b} [label=\"{weight}\"{style}];").unwrap();
+    }
+    out.push_str("}\n");
+    out
 }
 
-fn part1(input: &str) -> usize {
-    let cave = parse_nodes(input);
-    let start = cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap();
-    let cave = prune_nodes(start, &cave);
-    let start = cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap();
-    // println!("{:?}", Dot::new(&cave));
+/// `to_dot` for the tunnels in `input`, or only the valves worth opening if `pruned`,
+/// highlighting where `plan` goes from `start`
+fn export_dot(input: &str, start: &str, pruned: bool, plan: Option<&Plan>) -> String {
+    let mut cave = parse_nodes(input);
+    if pruned {
+        let start = cave.node_indices().find(|nx| cave[*nx].1 == start).unwrap();
+        cave = prune_nodes(start, &cave);
+    }
+    let route = plan
+        .map(|plan| {
+            once(start)
+                .chain(plan.opens.iter().map(|(name, _)| name.as_str()))
+                .collect_vec()
+        })
+        .unwrap_or_default();
+    to_dot(&cave, &route)
+}
 
-    let distance_map = floyd_warshall(&cave, |edge| *edge.weight()).unwrap();
-    let closed_valves = cave.node_indices().unique().collect();
-    visit(&start, 30, 0, &closed_valves, 0, &cave, &distance_map)
+/// One agent's valves in the order they are opened, with the minute each one opens in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Plan {
+    opens: Vec<(String, usize)>,
+    pressure: usize,
 }
 
-fn part2(input: &str) -> usize {
-    let cave = parse_nodes(input);
-    let start = cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap();
-    let cave = prune_nodes(start, &cave);
-    let start = cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap();
-    // println!("{:?}", Dot::new(&cave));
+/// The valves worth opening and how long it takes to walk between them
+struct Valves {
+    /// Flow rate of each valve with any flow, valve `i` is bit `i` in a set of valves
+    flows: Vec<usize>,
+    /// Valve names, followed by the start's
+    names: Vec<String>,
+    /// Minutes from one valve to another, the start is the last row and column
+    dist: Vec<Vec<usize>>,
+}
 
-    let distance_map = floyd_warshall(&cave, |edge| *edge.weight()).unwrap();
-    let closed_valves = cave.node_indices().unique().collect_vec();
+impl Valves {
+    fn new(input: &str, start: &str) -> Self {
+        let cave = parse_nodes(input);
+        let start = cave
+            .node_indices()
+            .find(|nx| cave[*nx].1 == start)
+            .unwrap_or_else(|| panic!("No valve {start}"));
+        let reachable = dijkstra(&cave, start, None, |edge| *edge.weight());
+        let nodes = cave
+            .node_indices()
+            .filter(|nx| cave[*nx].0 > 0 && reachable.contains_key(nx))
+            .chain(once(start))
+            .collect_vec();
+        Valves {
+            flows: nodes[..nodes.len() - 1]
+                .iter()
+                .map(|nx| cave[*nx].0)
+                .collect(),
+            names: nodes.iter().map(|nx| cave[*nx].1.clone()).collect(),
+            // everything is reachable from the start, so from each other too
+            dist: distance_matrix(&cave, &nodes)
+                .into_iter()
+                .map(|row| row.into_iter().map(Option::unwrap).collect())
+                .collect(),
+        }
+    }
 
-    let mut solutions: HashMap<BTreeSet<NodeIndex>, usize> = HashMap::new();
-    let subsets = closed_valves.clone().into_iter().powerset().collect_vec();
-    let subsets: Vec<BTreeSet<NodeIndex>> = subsets
-        .into_iter()
-        .map(|v| v.into_iter().collect())
-        .collect();
+    fn start(&self) -> usize {
+        self.flows.len()
+    }
 
-    for possible in subsets {
-        solutions.insert(
-            possible.clone(),
-            visit(&start, 26, 0, &possible, 0, &cave, &distance_map),
-        );
+    /// Most pressure released within `minutes` by opening exactly the valves in each set
+    fn best_per_set(&self, minutes: usize) -> Vec<usize> {
+        let mut best = vec![0; 1 << self.flows.len()];
+        self.explore(self.start(), minutes, 0, 0, &mut best);
+        best
+    }
+
+    fn explore(
+        &self,
+        pos: usize,
+        remaining: usize,
+        opened: usize,
+        released: usize,
+        best: &mut [usize],
+    ) {
+        best[opened] = best[opened].max(released);
+        for (next, flow) in self.flows.iter().enumerate() {
+            let cost = self.dist[pos][next] + 1;
+            if opened & 1 << next != 0 || cost >= remaining {
+                continue;
+            }
+            let remaining = remaining - cost;
+            self.explore(
+                next,
+                remaining,
+                opened | 1 << next,
+                released + remaining * flow,
+                best,
+            );
+        }
     }
+}
 
-    let all_valves_set: HashSet<&NodeIndex> = closed_valves.iter().collect();
-    solutions
-        .keys()
-        .map(|solution| {
-            let solution_set: HashSet<&NodeIndex> = solution.iter().collect();
-            let other = all_valves_set.sub(&solution_set);
+impl Valves {
+    /// Best route for one agent with `minutes` that only opens valves from `allowed`,
+    /// returning the pressure and the valves opened with the minutes left after each, last
+    /// one first
+    fn best_route(
+        &self,
+        pos: usize,
+        remaining: usize,
+        allowed: usize,
+    ) -> (usize, Vec<(usize, usize)>) {
+        let mut best = (0, Vec::new());
+        for (next, flow) in self.flows.iter().enumerate() {
+            let cost = self.dist[pos][next] + 1;
+            if allowed & 1 << next == 0 || cost >= remaining {
+                continue;
+            }
+            let remaining = remaining - cost;
+            let (released, mut route) = self.best_route(next, remaining, allowed & !(1 << next));
+            if released + remaining * flow > best.0 {
+                route.push((next, remaining));
+                best = (released + remaining * flow, route);
+            }
+        }
+        best
+    }
 
-            let lhs: BTreeSet<NodeIndex> = solution_set.iter().map(|v| **v).collect();
-            let rhs: BTreeSet<NodeIndex> = other.iter().map(|v| **v).collect();
-            solutions[&lhs] + solutions[&rhs]
-        })
-        .max()
-        .unwrap()
+    fn plan(&self, allowed: usize, minutes: usize) -> Plan {
+        let (pressure, route) = self.best_route(self.start(), minutes, allowed);
+        Plan {
+            opens: route
+                .into_iter()
+                .rev()
+                .map(|(valve, remaining)| (self.names[valve].clone(), minutes - remaining))
+                .collect(),
+            pressure,
+        }
+    }
+
+    /// Shares the valves between `agents` that each have `minutes` to release as much
+    /// pressure as possible together
+    fn plans(&self, agents: usize, minutes: usize) -> Vec<Plan> {
+        if agents == 0 {
+            return Vec::new();
+        }
+        let within = best_within_sets(self.best_per_set(minutes));
+        let all = within.len() - 1;
+
+        // best[k][set] is the most k + 1 agents release with the valves in `set`, share[k][set]
+        // what the first of them gets to open. Only the full set is needed for all agents
+        let mut best = vec![within.clone()];
+        let mut share = vec![(0..=all).collect_vec()];
+        for k in 1..agents {
+            let sets = if k + 1 == agents { all..=all } else { 0..=all };
+            let mut level = vec![0; all + 1];
+            let mut choice = vec![0; all + 1];
+            for set in sets {
+                let mut sub = set;
+                loop {
+                    let released = within[sub] + best[k - 1][set ^ sub];
+                    if released > level[set] {
+                        level[set] = released;
+                        choice[set] = sub;
+                    }
+                    if sub == 0 {
+                        break;
+                    }
+                    sub = (sub - 1) & set;
+                }
+            }
+            best.push(level);
+            share.push(choice);
+        }
+
+        let mut set = all;
+        let mut plans = Vec::new();
+        for k in (0..agents).rev() {
+            plans.push(self.plan(share[k][set], minutes));
+            set ^= share[k][set];
+        }
+        plans
+    }
+}
+
+/// Turns the best for exactly each set into the best using any subset of it
+fn best_within_sets(mut best: Vec<usize>) -> Vec<usize> {
+    let n_valves = best.len().trailing_zeros();
+    for valve in 0..n_valves {
+        for set in 0..best.len() {
+            if set & 1 << valve != 0 {
+                best[set] = best[set].max(best[set ^ 1 << valve]);
+            }
+        }
+    }
+    best
+}
+
+/// Plans for `agents` agents starting at valve `start` with `minutes` each
+fn most_pressure(input: &str, start: &str, minutes: usize, agents: usize) -> Vec<Plan> {
+    Valves::new(input, start).plans(agents, minutes)
+}
+
+fn part1(input: &str) -> usize {
+    most_pressure(input, "AA", 30, 1)
+        .iter()
+        .map(|plan| plan.pressure)
+        .sum()
+}
+
+fn part2(input: &str) -> usize {
+    // you and one elephant
+    most_pressure(input, "AA", 26, 2)
+        .iter()
+        .map(|plan| plan.pressure)
+        .sum()
+}
+
+/// The tunnels as Graphviz source before and after pruning, with part 1's route
+pub fn exports(input: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let plans = most_pressure(input, "AA", 30, 1);
+    vec![
+        (
+            "tunnels.dot",
+            export_dot(input, "AA", false, Some(&plans[0])).into_bytes(),
+        ),
+        (
+            "pruned.dot",
+            export_dot(input, "AA", true, Some(&plans[0])).into_bytes(),
+        ),
+    ]
 }
 
-#[allow(dead_code)]
 pub fn main() -> std::io::Result<()> {
     let input = &read_input_to_string(16)?;
     dbg!(part1(input));
@@ -181,9 +402,82 @@ Valve II has flow rate=0; tunnels lead to valves AA, JJ
 Valve JJ has flow rate=21; tunnel leads to valve II";
     let cave = parse_nodes(input);
     let start = cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap();
-    // println!("{:?}", Dot::new(&cave));
-    let _cave = prune_nodes(start, &cave);
-    // println!("{:?}", Dot::new(&cave));
+    let pruned = prune_nodes(start, &cave);
+    assert_eq!(
+        pruned.node_weights().map(|(_, name)| name).join(","),
+        "AA,BB,CC,DD,EE,HH,JJ"
+    );
+    let mut tunnels = pruned
+        .edge_references()
+        .map(|e| {
+            format!(
+                "{}-{}={}",
+                pruned[e.source()].1,
+                pruned[e.target()].1,
+                e.weight()
+            )
+        })
+        .collect_vec();
+    tunnels.sort();
+    assert_eq!(
+        tunnels,
+        ["AA-BB=1", "AA-DD=1", "AA-JJ=2", "BB-CC=1", "CC-DD=1", "DD-EE=1", "EE-HH=3"]
+    );
+    check_pruned(&cave, start);
+}
+
+/// Walking the pruned graph between any two valves it kept must take as long as in `cave`
+#[cfg(test)]
+fn check_pruned(cave: &Cave, start: NodeIndex) {
+    let pruned = prune_nodes(start, cave);
+    let find = |graph: &Cave, name: &str| graph.node_indices().find(|nx| graph[*nx].1 == name);
+    let kept = cave
+        .node_indices()
+        .filter(|nx| *nx == start || cave[*nx].0 > 0)
+        .collect_vec();
+    assert_eq!(pruned.node_count(), kept.len());
+    for from in &kept {
+        let raw = dijkstra(cave, *from, None, |edge| *edge.weight());
+        let short = dijkstra(
+            &pruned,
+            find(&pruned, &cave[*from].1).unwrap(),
+            None,
+            |edge| *edge.weight(),
+        );
+        for to in &kept {
+            let to_pruned = find(&pruned, &cave[*to].1).unwrap();
+            assert_eq!(raw.get(to), short.get(&to_pruned));
+        }
+    }
+}
+
+#[test]
+fn prune_random_caves() {
+    let mut rng = XorShift::new(16);
+    for _ in 0..300 {
+        let n_nodes = 2 + rng.below(14);
+        let mut cave = Cave::default();
+        for i in 0..n_nodes {
+            let flow = if rng.below(3) == 0 {
+                1 + rng.below(25)
+            } else {
+                0
+            };
+            let name = format!(
+                "{}{}",
+                (b'A' + (i / 26) as u8) as char,
+                (b'A' + (i % 26) as u8) as char
+            );
+            cave.add_node((flow, name));
+        }
+        for _ in 0..rng.below(2 * n_nodes) {
+            let (a, b) = (rng.below(n_nodes), rng.below(n_nodes));
+            if a != b {
+                cave.update_edge(NodeIndex::new(a), NodeIndex::new(b), 1);
+            }
+        }
+        check_pruned(&cave, NodeIndex::new(rng.below(n_nodes)));
+    }
 }
 
 #[test]
@@ -202,11 +496,148 @@ Valve JJ has flow rate=21; tunnel leads to valve II";
     assert_eq!(part2(input), 1707);
 }
 
+/// Checks the plans can be walked in time, share no valves and release what they claim
+#[cfg(test)]
+fn check_plans(input: &str, start: &str, minutes: usize, plans: &[Plan]) {
+    let valves = Valves::new(input, start);
+    let index = |name: &str| valves.names.iter().position(|n| n == name).unwrap();
+    let opened = plans
+        .iter()
+        .flat_map(|plan| plan.opens.iter().map(|(name, _)| name))
+        .collect_vec();
+    assert_eq!(opened.iter().unique().count(), opened.len());
+    for plan in plans {
+        let (mut pos, mut minute, mut pressure) = (valves.start(), 0, 0);
+        for (name, opens) in &plan.opens {
+            let valve = index(name);
+            // walking there and a minute to open it
+            assert!(minute + valves.dist[pos][valve] < *opens);
+            assert!(*opens < minutes);
+            pressure += valves.flows[valve] * (minutes - opens);
+            (pos, minute) = (valve, *opens);
+        }
+        assert_eq!(pressure, plan.pressure);
+    }
+}
+
+#[test]
+fn example_plans() {
+    let input = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+    fn opens(plan: &Plan) -> Vec<(&str, usize)> {
+        plan.opens
+            .iter()
+            .map(|(name, minute)| (name.as_str(), *minute))
+            .collect()
+    }
+
+    let plans = most_pressure(input, "AA", 30, 1);
+    assert_eq!(
+        opens(&plans[0]),
+        [
+            ("DD", 2),
+            ("BB", 5),
+            ("JJ", 9),
+            ("HH", 17),
+            ("EE", 21),
+            ("CC", 24)
+        ]
+    );
+    check_plans(input, "AA", 30, &plans);
+
+    let plans = most_pressure(input, "AA", 26, 2);
+    let mut routes = plans.iter().map(opens).collect_vec();
+    routes.sort();
+    assert_eq!(
+        routes,
+        [
+            vec![("DD", 2), ("HH", 7), ("EE", 11)],
+            vec![("JJ", 3), ("BB", 7), ("CC", 9)]
+        ]
+    );
+    check_plans(input, "AA", 26, &plans);
+
+    // more agents never hurt, and enough of them open everything
+    let mut previous = 0;
+    for agents in 1..=7 {
+        let plans = most_pressure(input, "AA", 20, agents);
+        check_plans(input, "AA", 20, &plans);
+        let total = plans.iter().map(|plan| plan.pressure).sum::<usize>();
+        assert!(total >= previous);
+        previous = total;
+    }
+    let plans = most_pressure(input, "AA", 20, 6);
+    assert_eq!(plans.iter().map(|plan| plan.opens.len()).sum::<usize>(), 6);
+
+    // starting elsewhere, or without any time
+    let plans = most_pressure(input, "HH", 30, 2);
+    check_plans(input, "HH", 30, &plans);
+    // HH is worth opening straight away
+    assert!(plans.iter().any(|plan| opens(plan)[0] == ("HH", 1)));
+    assert!(most_pressure(input, "AA", 1, 2)
+        .iter()
+        .all(|plan| plan.opens.is_empty() && plan.pressure == 0));
+    assert!(most_pressure(input, "AA", 30, 0).is_empty());
+}
+
+#[test]
+fn example_dot() {
+    let input = "Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+    assert_eq!(
+        export_dot(input, "AA", true, None),
+        include_str!("sample.dot")
+    );
+
+    let plan = Plan {
+        opens: vec![("JJ".into(), 3), ("HH".into(), 12)],
+        pressure: 0,
+    };
+    let dot = export_dot(input, "AA", false, Some(&plan));
+    for line in [
+        "    AA [label=\"AA\\n0\", color=red, penwidth=2];",
+        "    II [label=\"II\\n0\", color=red, penwidth=2];",
+        "    BB [label=\"BB\\n13\"];",
+        "    AA -- II [label=\"1\", color=red, penwidth=2];",
+        "    GG -- HH [label=\"1\", color=red, penwidth=2];",
+        "    BB -- CC [label=\"1\"];",
+    ] {
+        assert!(dot.contains(line), "{line} missing from {dot}");
+    }
+    assert_eq!(dot.matches("--").count(), 10);
+    assert_eq!(dot.matches("red").count(), 8 + 7);
+}
+
 #[test]
 fn task() {
     let input = &read_input_to_string(16).unwrap();
     assert_eq!(part1(input), 1376);
-    // assert_eq!(part2(input), ());
+    assert_eq!(part2(input), 1933);
+
+    let plans = most_pressure(input, "AA", 26, 3);
+    check_plans(input, "AA", 26, &plans);
+
+    let cave = parse_nodes(input);
+    check_pruned(
+        &cave,
+        cave.node_indices().find(|nx| cave[*nx].1 == *"AA").unwrap(),
+    );
 }
 
 #[bench]
@@ -1,116 +1,246 @@
 extern crate test;
 
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::str::FromStr;
 
+use anyhow::{bail, ensure, Result};
 use itertools::Itertools;
 use ndarray::{Array2, Axis};
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::{pretty_print, read_input_to_string};
+use crate::{
+    animate::{Frame, Recorder},
+    cycle::{extrapolate, CycleMethod},
+    utils::{pretty_print, read_input_to_string},
+};
 
 const WIDTH: usize = 7;
 
-enum ShapeType {
-    HorizontalLine,
-    Cross,
-    Angle,
-    VerticalLine,
-    Square,
-}
+/// The rocks from the puzzle, in the order they fall
+const ROCKS: &str = "####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
 
-struct Shape {
-    positions: Vec<(usize, usize)>,
+#
+#
+#
+#
+
+##
+##";
+
+/// Row bitmasks from the bottom up, bit `x` is `x` columns right of the rock's left edge
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rock {
+    rows: Vec<u64>,
+    width: usize,
 }
 
-impl Shape {
-    fn initial(kind: &ShapeType, height: usize) -> Self {
-        let mut positions: Vec<_> = match kind {
-            ShapeType::HorizontalLine => [(2, 0), (3, 0), (4, 0), (5, 0)].into(),
-            ShapeType::Cross => [(3, 0), (2, 1), (3, 1), (4, 1), (3, 2)].into(),
-            ShapeType::Angle => [(2, 0), (3, 0), (4, 0), (4, 1), (4, 2)].into(),
-            ShapeType::VerticalLine => [(2, 0), (2, 1), (2, 2), (2, 3)].into(),
-            ShapeType::Square => [(2, 0), (3, 0), (2, 1), (3, 1)].into(),
-        };
-        positions.iter_mut().for_each(|(_, y)| *y += height);
-        Shape { positions }
-    }
-
-    fn move_left(&mut self, occupied: &HashSet<(usize, usize)>) {
-        if self.positions.iter().all(|(x, _)| x > &0) {
-            let moved = self
-                .positions
-                .iter()
-                .map(|(x, y)| (x - 1, *y))
-                .collect_vec();
-            if moved.iter().all(|(x, y)| !occupied.contains(&(*x, *y))) {
-                self.positions = moved;
+impl FromStr for Rock {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rows = Vec::new();
+        for line in s.lines().rev() {
+            let mut row = 0;
+            for (x, c) in line.trim_end().chars().enumerate() {
+                match c {
+                    '#' if x < 64 => row |= 1 << x,
+                    '#' => bail!("Rock is wider than 64 columns:\n{s}"),
+                    '.' | ' ' => (),
+                    _ => bail!("Unknown rock character {c}"),
+                }
             }
+            rows.push(row);
         }
+        ensure!(!rows.is_empty(), "Empty rock");
+        let mask = rows.iter().fold(0, |acc, row| acc | row);
+        ensure!(
+            mask & 1 == 1 && rows[0] != 0 && *rows.last().unwrap() != 0,
+            "Rock must touch its left, bottom and top edge:\n{s}"
+        );
+        Ok(Rock {
+            rows,
+            width: (u64::BITS - mask.leading_zeros()) as usize,
+        })
     }
+}
 
-    fn move_right(&mut self, occupied: &HashSet<(usize, usize)>) {
-        if self.positions.iter().all(|(x, _)| x < &6) {
-            let moved = self
-                .positions
-                .iter()
-                .map(|(x, y)| (x + 1, *y))
-                .collect_vec();
-            if moved.iter().all(|(x, y)| !occupied.contains(&(*x, *y))) {
-                self.positions = moved;
-            }
+/// Rocks drawn with `#`, separated by blank lines
+fn parse_rocks(art: &str) -> Result<Vec<Rock>> {
+    let rocks: Vec<Rock> = art
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(str::parse)
+        .try_collect()?;
+    ensure!(!rocks.is_empty(), "No rocks in {art}");
+    Ok(rocks)
+}
+
+/// A rock while it is falling
+#[derive(Debug, Clone, Copy)]
+struct Shape<'a> {
+    rock: &'a Rock,
+    /// Column of the left edge
+    x: usize,
+    /// Row of the bottom edge
+    y: usize,
+}
+
+impl<'a> Shape<'a> {
+    fn rows(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.rock
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(dy, row)| (self.y + dy, row << self.x))
+    }
+
+    fn try_move(&mut self, moved: Shape<'a>, tower: &Tower) -> Option<()> {
+        if tower.collides(&moved) {
+            None
+        } else {
+            *self = moved;
+            Some(())
         }
     }
 
-    fn move_down(&mut self, occupied: &HashSet<(usize, usize)>) -> Option<()> {
-        if self.positions.iter().all(|(_, y)| y > &0) {
-            let moved = self
-                .positions
-                .iter()
-                .map(|(x, y)| (*x, y - 1))
-                .collect_vec();
-            if moved.iter().all(|(x, y)| !occupied.contains(&(*x, *y))) {
-                self.positions = moved;
-                return Some(());
-            }
+    fn move_left(&mut self, tower: &Tower) {
+        if self.x > 0 {
+            self.try_move(
+                Shape {
+                    x: self.x - 1,
+                    ..*self
+                },
+                tower,
+            );
+        }
+    }
+
+    fn move_right(&mut self, tower: &Tower) {
+        if self.x + self.rock.width < tower.width {
+            self.try_move(
+                Shape {
+                    x: self.x + 1,
+                    ..*self
+                },
+                tower,
+            );
+        }
+    }
+
+    fn move_down(&mut self, tower: &Tower) -> Option<()> {
+        if self.y > 0 {
+            self.try_move(
+                Shape {
+                    y: self.y - 1,
+                    ..*self
+                },
+                tower,
+            )
+        } else {
+            None
         }
-        None
     }
 }
 
+#[derive(Clone)]
 struct Tower {
-    heights: [(usize, usize); WIDTH],
-    occupied: HashSet<(usize, usize)>,
+    width: usize,
+    /// Occupied cells per row from the lowest kept row up to the highest rock, bit `x` is
+    /// column `x` and the floor is the first row
+    rows: Vec<u64>,
+    /// Rows dropped from the bottom since no rock can reach them anymore
+    trimmed: usize,
 }
 
 impl Tower {
-    fn new() -> Self {
-        let mut heights = [(0, 0); WIDTH];
-        heights
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, (x, _))| *x += i);
-        let occupied = HashSet::from(heights);
-        Tower { heights, occupied }
+    fn new(width: usize) -> Self {
+        let mut tower = Tower {
+            width,
+            rows: Vec::new(),
+            trimmed: 0,
+        };
+        tower.rows.push(tower.full_row());
+        tower
+    }
+
+    fn full_row(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    /// Row a new rock's bottom is placed at, leaving three empty rows
+    fn spawn_y(&self) -> usize {
+        self.rows.len() + 3
     }
 
     fn top(&self) -> usize {
-        *self.heights.iter().map(|(_, y)| y).max().unwrap()
+        self.trimmed + self.rows.len() - 1
     }
 
-    fn top_diffs(&self) -> [usize; WIDTH] {
-        let mn = self.heights.iter().map(|(_, y)| y).min().unwrap();
-        self.heights
-            .iter()
-            .map(|(_, y)| y - mn)
-            .collect_vec()
-            .try_into()
-            .unwrap()
+    fn collides(&self, shape: &Shape) -> bool {
+        shape
+            .rows()
+            .any(|(y, row)| matches!(self.rows.get(y), Some(occupied) if occupied & row != 0))
+    }
+
+    fn settle(&mut self, shape: &Shape) {
+        let needed = shape.y + shape.rock.rows.len();
+        if needed > self.rows.len() {
+            self.rows.resize(needed, 0);
+        }
+        for (y, row) in shape.rows() {
+            self.rows[y] |= row;
+        }
+        self.trim();
+    }
+
+    /// Empty cells a falling rock can still reach, per row from the one above the top
+    /// downwards. Rocks only move sideways and down, so a row can be done in one pass
+    fn surface(&self) -> Vec<u64> {
+        let full_row = self.full_row();
+        let mut surface = vec![full_row];
+        for row in self.rows.iter().rev() {
+            let empty = !row & full_row;
+            let mut reachable = surface.last().unwrap() & empty;
+            loop {
+                let spread = (reachable | reachable << 1 | reachable >> 1) & empty;
+                if spread == reachable {
+                    break;
+                }
+                reachable = spread;
+            }
+            if reachable == 0 {
+                break;
+            }
+            surface.push(reachable);
+        }
+        surface
+    }
+
+    /// Drops every row below the one supporting the lowest reachable cell
+    fn trim(&mut self) {
+        let lowest_reachable = self.rows.len() + 1 - self.surface().len();
+        let removable = lowest_reachable.saturating_sub(1);
+        self.rows.drain(..removable);
+        self.trimmed += removable;
     }
 
     fn visualize(&self) -> String {
-        let mut arr = Array2::<char>::from_elem((self.top() + 1, WIDTH), '.');
-        self.occupied.iter().for_each(|(x, y)| arr[(*y, *x)] = '#');
+        let mut arr = Array2::<char>::from_elem((self.rows.len(), self.width), '.');
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in 0..self.width {
+                if row & (1 << x) != 0 {
+                    arr[(y, x)] = '#';
+                }
+            }
+        }
         arr.invert_axis(Axis(0));
 
         pretty_print(&arr)
@@ -122,88 +252,91 @@ impl Tower {
     }
 }
 
-const SHAPES: [ShapeType; 5] = [
-    ShapeType::HorizontalLine,
-    ShapeType::Cross,
-    ShapeType::Angle,
-    ShapeType::VerticalLine,
-    ShapeType::Square,
-];
+#[derive(Clone)]
+struct Chamber<'a> {
+    tower: Tower,
+    jets: &'a [char],
+    rocks: &'a [Rock],
+    jet_idx: usize,
+    rock_idx: usize,
+}
 
-fn parts(input: &str, shapes_to_land: usize) -> usize {
-    let mut seen = HashMap::new();
-    let mut tower = Tower::new();
-    let mut jets = input.trim().char_indices().cycle();
-    let mut shapes = SHAPES.iter().enumerate().cycle();
-    let mut next_shape = true;
-    let mut shape = Shape::initial(&ShapeType::HorizontalLine, 3); // Placeholder
-    let mut shape_count = 0;
-
-    let mut shape_idx;
-    let mut jet_idx = 0;
-
-    let mut extra_height = 0;
-
-    // tower.print_visualize();
-    // println!();
-
-    let mut has_skipped = false;
-
-    while shape_count < shapes_to_land {
-        if next_shape {
-            let next_shape_type = shapes.next().unwrap();
-            shape_idx = next_shape_type.0;
-            shape = Shape::initial(next_shape_type.1, tower.top() + 4);
-            next_shape = false;
-
-            if !has_skipped {
-                let key = (shape_idx, jet_idx, tower.top_diffs());
-                if let Entry::Vacant(e) = seen.entry(key) {
-                    e.insert((shape_count, tower.top()));
-                } else {
-                    let (last_shape_count, last_height) = seen[&key];
-                    let shapes_since = shape_count - last_shape_count;
-                    let height_since = tower.top() - last_height;
-                    let remaining_shape_count = shapes_to_land - shape_count;
-
-                    let repeats = remaining_shape_count / shapes_since;
-                    let remaining = remaining_shape_count % shapes_since;
-
-                    extra_height = repeats * height_since;
-                    shape_count += repeats * shapes_since;
-
-                    dbg!(key);
-                    dbg!(last_shape_count, last_height);
-                    dbg!(shapes_since, height_since);
-                    dbg!(remaining);
-
-                    has_skipped = true;
-                }
-            }
+impl<'a> Chamber<'a> {
+    fn new(jets: &'a [char], rocks: &'a [Rock], width: usize) -> Result<Self> {
+        ensure!(width <= 64, "Chamber can be at most 64 wide, got {width}");
+        ensure!(!jets.is_empty() && !rocks.is_empty(), "Need jets and rocks");
+        if let Some(rock) = rocks.iter().find(|rock| rock.width + 2 > width) {
+            bail!("{rock:?} does not fit in a chamber {width} wide");
         }
-        let next_jets = jets.next().unwrap();
-        jet_idx = next_jets.0;
+        Ok(Chamber {
+            tower: Tower::new(width),
+            jets,
+            rocks,
+            jet_idx: 0,
+            rock_idx: 0,
+        })
+    }
 
-        match next_jets.1 {
-            '<' => shape.move_left(&tower.occupied),
-            '>' => shape.move_right(&tower.occupied),
-            _ => panic!("bad jet {}", next_jets.1),
+    /// Drops the next rock until it comes to rest
+    fn drop_rock(&mut self) {
+        let mut shape = Shape {
+            rock: &self.rocks[self.rock_idx],
+            x: 2,
+            y: self.tower.spawn_y(),
+        };
+        self.rock_idx = (self.rock_idx + 1) % self.rocks.len();
+        loop {
+            let jet = self.jets[self.jet_idx];
+            self.jet_idx = (self.jet_idx + 1) % self.jets.len();
+            match jet {
+                '<' => shape.move_left(&self.tower),
+                '>' => shape.move_right(&self.tower),
+                _ => panic!("bad jet {jet}"),
+            }
+            if shape.move_down(&self.tower).is_none() {
+                break;
+            }
         }
-        if shape.move_down(&tower.occupied).is_none() {
-            shape.positions.iter().for_each(|(x, y)| {
-                tower.occupied.insert((*x, *y));
-                if y > &tower.heights[*x].1 {
-                    tower.heights[*x].1 = *y;
-                }
-            });
-            shape_count += 1;
-            next_shape = true;
+        self.tower.settle(&shape);
+    }
 
-            // tower.print_visualize();
-            // println!();
-        }
+    fn key(&self) -> (usize, usize, Vec<u64>) {
+        (self.rock_idx, self.jet_idx, self.tower.surface())
+    }
+
+    fn height(&self) -> usize {
+        self.tower.top()
     }
-    tower.top() + extra_height
+}
+
+/// Height of the tower after `rocks_to_land` rocks drawn in `rock_art` fell into a chamber
+/// `width` wide
+fn tower_height(input: &str, rock_art: &str, width: usize, rocks_to_land: usize) -> Result<usize> {
+    tower_height_with(input, rock_art, width, rocks_to_land, CycleMethod::Table)
+}
+
+/// `tower_height`, finding the cycle with `method`
+fn tower_height_with(
+    input: &str,
+    rock_art: &str,
+    width: usize,
+    rocks_to_land: usize,
+    method: CycleMethod,
+) -> Result<usize> {
+    let jets = input.trim().chars().collect_vec();
+    let rocks = parse_rocks(rock_art)?;
+    Ok(extrapolate(
+        &Chamber::new(&jets, &rocks, width)?,
+        rocks_to_land,
+        Chamber::drop_rock,
+        Chamber::key,
+        Chamber::height,
+        method,
+    ))
+}
+
+fn parts(input: &str, shapes_to_land: usize) -> usize {
+    tower_height(input, ROCKS, WIDTH, shapes_to_land).unwrap()
 }
 
 fn part1(input: &str) -> usize {
@@ -214,7 +347,20 @@ fn part2(input: &str) -> usize {
     parts(input, 1000000000000)
 }
 
-pub fn main() -> std::io::Result<()> {
+/// The part 1 tower growing one rock at a time, following its top
+pub fn animate(input: &str) -> Vec<Frame> {
+    let jets = input.trim().chars().collect_vec();
+    let rocks = parse_rocks(ROCKS).unwrap();
+    let mut chamber = Chamber::new(&jets, &rocks, WIDTH).unwrap();
+    let mut recorder = Recorder::new();
+    for _ in 0..2022 {
+        chamber.drop_rock();
+        recorder.record(|| Frame::focused(chamber.tower.visualize(), (0, WIDTH / 2)));
+    }
+    recorder.into_frames()
+}
+
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(17)?;
     dbg!(part1(input));
     dbg!(part2(input));
@@ -233,7 +379,102 @@ fn example() {
 fn task() {
     let input = &read_input_to_string(17).unwrap();
     assert_eq!(part1(input), 3219);
-    // assert_eq!(part2(input), ());
+    assert_eq!(part2(input), 1582758620701);
+    // the constant memory cycle detectors agree with the table
+    for method in [CycleMethod::Floyd, CycleMethod::Brent] {
+        assert_eq!(
+            tower_height_with(input, ROCKS, WIDTH, 1000000000000, method).unwrap(),
+            1582758620701
+        );
+    }
+}
+
+#[test]
+fn tiny_rocks() {
+    let jets = ['>'];
+    let rocks = parse_rocks("#\n\n##").unwrap();
+    assert_eq!(
+        rocks[1],
+        Rock {
+            rows: vec![0b11],
+            width: 2
+        }
+    );
+    let mut chamber = Chamber::new(&jets, &rocks, 4).unwrap();
+    chamber.drop_rock();
+    chamber.drop_rock();
+    assert_eq!(chamber.tower.visualize(), "..##\n...#\n####");
+    chamber.drop_rock();
+    assert_eq!(chamber.tower.visualize(), "...#\n..##\n...#\n####");
+    assert_eq!(chamber.height(), 3);
+
+    let jets = ['<'];
+    let rocks = parse_rocks("#.\n##").unwrap();
+    let mut chamber = Chamber::new(&jets, &rocks, 4).unwrap();
+    chamber.drop_rock();
+    chamber.drop_rock();
+    assert_eq!(chamber.tower.visualize(), "#...\n##..\n#...\n##..\n####");
+
+    assert!(Chamber::new(&jets, &rocks, 3).is_err());
+    assert!(parse_rocks(".#\n.#").is_err());
+    assert!(parse_rocks("#\n?").is_err());
+    assert_eq!(tower_height(">", "#", 3, 1000).unwrap(), 1000);
+    assert_eq!(tower_height("<>", "###", 5, 10).unwrap(), 10);
+}
+
+#[test]
+fn wide_chamber() {
+    let input = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+    let narrow = tower_height(input, ROCKS, 7, 2022).unwrap();
+    let wide = tower_height(input, ROCKS, 20, 2022).unwrap();
+    assert!(wide < narrow);
+    assert!(tower_height(input, ROCKS, 5, 2022).is_err());
+    assert!(tower_height(input, ROCKS, 65, 2022).is_err());
+}
+
+#[test]
+fn task_trimmed() {
+    let jets = read_input_to_string(17)
+        .unwrap()
+        .trim()
+        .chars()
+        .collect_vec();
+    let rocks = parse_rocks(ROCKS).unwrap();
+    let mut chamber = Chamber::new(&jets, &rocks, WIDTH).unwrap();
+    for _ in 0..100_000 {
+        chamber.drop_rock();
+        assert!(chamber.tower.rows.len() < 100);
+    }
+}
+
+#[test]
+fn extrapolation_matches_simulation() {
+    for input in [
+        ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>",
+        &read_input_to_string(17).unwrap(),
+    ] {
+        let jets = input.trim().chars().collect_vec();
+        let rocks = parse_rocks(ROCKS).unwrap();
+        let mut chamber = Chamber::new(&jets, &rocks, WIDTH).unwrap();
+        for _ in 0..10_000 {
+            chamber.drop_rock();
+        }
+        for method in [CycleMethod::Table, CycleMethod::Floyd, CycleMethod::Brent] {
+            assert_eq!(
+                tower_height_with(input, ROCKS, WIDTH, 2022, method).unwrap(),
+                part1(input)
+            );
+            let extrapolated = extrapolate(
+                &Chamber::new(&jets, &rocks, WIDTH).unwrap(),
+                10_000,
+                Chamber::drop_rock,
+                Chamber::key,
+                Chamber::height,
+                method,
+            );
+            assert_eq!(extrapolated, chamber.height());
+        }
+    }
 }
 
 #[bench]
@@ -1,14 +1,31 @@
 extern crate test;
 
+use anyhow::{bail, Result};
 use itertools::Itertools;
-use ndarray::Array3;
-use ndarray_ndimage::{convolve, pad, BorderMode, PadMode};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use ndarray::{indices, Array3, Axis};
+use ndarray_ndimage::{convolve, BorderMode};
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::read_input_to_string;
+#[cfg(test)]
+use crate::export::decode_gif;
+#[cfg(test)]
+use crate::utils::XorShift;
+use crate::{
+    export::{gif, Palette},
+    utils::read_input_to_string,
+};
 
 type Map = Array3<u8>;
+type Cube = [i64; 3];
+
+/// Largest droplet bounding box, in cells, that is still worked on as a dense grid
+const DENSE_LIMIT: usize = 1 << 24;
 
 const STAR: [[usize; 3]; 6] = [
     [1, 0, 0],
@@ -19,20 +36,145 @@ const STAR: [[usize; 3]; 6] = [
     [0, 0, usize::MAX],
 ];
 
-fn parse_input(input: &str) -> Map {
-    let mut arr = Array3::zeros((20, 20, 20));
+fn parse_cubes(input: &str) -> Vec<Cube> {
+    input
+        .lines()
+        .map(|line| {
+            line.split(',')
+                .map(|v| v.parse().unwrap())
+                .collect_vec()
+                .try_into()
+                .unwrap()
+        })
+        .collect()
+}
 
-    input.lines().for_each(|line| {
-        let idx: [usize; 3] = line
-            .split(',')
-            .map(|v| v.parse().unwrap())
-            .collect_vec()
-            .try_into()
-            .unwrap();
-        arr[idx] = 1;
-    });
+/// Lava in a grid sized to the droplet with a layer of air all around, and the coordinates of
+/// its `[0, 0, 0]` cell. `None` if the grid would need more than `DENSE_LIMIT` cells.
+fn dense(cubes: &[Cube]) -> Option<(Map, Cube)> {
+    let mut offset = [0; 3];
+    let mut shape = [0; 3];
+    for axis in 0..3 {
+        let (min, max) = cubes
+            .iter()
+            .map(|cube| cube[axis])
+            .minmax()
+            .into_option()
+            .unwrap_or((0, 0));
+        offset[axis] = min - 1;
+        shape[axis] = usize::try_from(max - min + 3).ok()?;
+    }
+    if shape
+        .iter()
+        .try_fold(1usize, |acc, len| acc.checked_mul(*len))?
+        > DENSE_LIMIT
+    {
+        return None;
+    }
 
-    arr
+    let mut arr = Array3::zeros(shape);
+    for cube in cubes {
+        arr[[0, 1, 2].map(|axis| (cube[axis] - offset[axis]) as usize)] = 1;
+    }
+    Some((arr, offset))
+}
+
+fn step(cube: Cube, dir: [usize; 3]) -> Cube {
+    [0, 1, 2].map(|axis| cube[axis] + dir[axis] as i64)
+}
+
+/// Groups the cubes into pieces that touch along faces, edges or corners
+fn clusters(lava: &HashSet<Cube>) -> Vec<Vec<Cube>> {
+    let mut seen = HashSet::new();
+    let mut clusters = Vec::new();
+    for start in lava {
+        if !seen.insert(*start) {
+            continue;
+        }
+        let mut cluster = vec![*start];
+        let mut i = 0;
+        while i < cluster.len() {
+            let cube = cluster[i];
+            for near in around(cube) {
+                if lava.contains(&near) && seen.insert(near) {
+                    cluster.push(near);
+                }
+            }
+            i += 1;
+        }
+        clusters.push(cluster);
+    }
+    clusters
+}
+
+/// The 26 cells sharing a face, edge or corner with `cube`
+fn around(cube: Cube) -> impl Iterator<Item = Cube> {
+    (0..27).filter(|i| *i != 13).map(move |i| {
+        [
+            cube[0] + i / 9 - 1,
+            cube[1] + i / 3 % 3 - 1,
+            cube[2] + i % 3 - 1,
+        ]
+    })
+}
+
+fn sparse_surface(cubes: &[Cube]) -> usize {
+    let lava: HashSet<Cube> = cubes.iter().copied().collect();
+    lava.iter()
+        .flat_map(|cube| STAR.map(|dir| step(*cube, dir)))
+        .filter(|side| !lava.contains(side))
+        .count()
+}
+
+/// Outside air right around each piece of the droplet, found without a dense grid. A piece is
+/// outside if the air left of it can be traced along x to the outside, or to the outer air of a
+/// piece further left that is already known to be outside.
+fn sparse_outside(lava: &HashSet<Cube>) -> HashSet<Cube> {
+    let mut rows: HashMap<[i64; 2], Vec<i64>> = HashMap::new();
+    for cube in lava {
+        rows.entry([cube[1], cube[2]]).or_default().push(cube[0]);
+    }
+    rows.values_mut().for_each(|row| row.sort_unstable());
+
+    let mut clusters = clusters(lava);
+    clusters.sort_by_key(|cluster| cluster.iter().map(|cube| cube[0]).min());
+
+    let mut outside = HashSet::new();
+    for cluster in clusters {
+        let mut start = *cluster.iter().min().unwrap();
+        start[0] -= 1;
+        let hit = rows
+            .get(&[start[1], start[2]])
+            .and_then(|row| row[..row.partition_point(|x| *x < start[0])].last());
+        if hit.is_some_and(|x| !outside.contains(&[x + 1, start[1], start[2]])) {
+            continue;
+        }
+
+        let piece: HashSet<Cube> = cluster.into_iter().collect();
+        let mut seen = HashSet::new();
+        let mut to_visit = vec![start];
+        while let Some(next) = to_visit.pop() {
+            if lava.contains(&next)
+                || !around(next).any(|near| piece.contains(&near))
+                || !seen.insert(next)
+            {
+                continue;
+            }
+            outside.insert(next);
+            to_visit.extend(STAR.map(|dir| step(next, dir)));
+        }
+    }
+    outside
+}
+
+/// Exterior surface without a dense grid, see `sparse_outside`
+fn sparse_exterior(cubes: &[Cube]) -> usize {
+    let lava: HashSet<Cube> = cubes.iter().copied().collect();
+    let outside = sparse_outside(&lava);
+    lava.iter()
+        .flat_map(|cube| STAR.map(|dir| step(*cube, dir)))
+        .filter(|side| outside.contains(side))
+        .count()
 }
 
 fn neighbor_mask() -> Map {
@@ -52,9 +194,8 @@ fn neighbors(map: &Map, mask: &Map) -> Map {
     convolve(map, mask, BorderMode::Constant(0), 0)
 }
 
-fn part1(input: &str) -> usize {
-    let arr = parse_input(input);
-    let neighbors = neighbors(&arr, &neighbor_mask());
+fn surface(arr: &Map) -> usize {
+    let neighbors = neighbors(arr, &neighbor_mask());
     neighbors
         .iter()
         .zip(arr.iter())
@@ -68,8 +209,9 @@ fn part1(input: &str) -> usize {
         .sum::<usize>()
 }
 
-fn flood_fill(map: &Map, start: [usize; 3]) -> Map {
-    let mut map = map.clone();
+/// Sets every empty cell reachable from `start` to `value`, returning those cells
+fn fill(map: &mut Map, start: [usize; 3], value: u8) -> Vec<[usize; 3]> {
+    let mut filled = Vec::new();
     let mut to_visit = Vec::from([start]);
 
     while let Some(next) = to_visit.pop() {
@@ -78,7 +220,8 @@ fn flood_fill(map: &Map, start: [usize; 3]) -> Map {
                 continue;
             }
 
-            *v = 2;
+            *v = value;
+            filled.push(next);
 
             for dir in STAR {
                 to_visit.push([
@@ -89,13 +232,204 @@ fn flood_fill(map: &Map, start: [usize; 3]) -> Map {
             }
         }
     }
+    filled
+}
+
+fn flood_fill(map: &Map, start: [usize; 3]) -> Map {
+    let mut map = map.clone();
+    fill(&mut map, start, 2);
     map
 }
 
-fn part2(input: &str) -> usize {
-    let arr = parse_input(input);
-    let arr = pad(&arr, &[[1, 1], [1, 1], [1, 1]], PadMode::Constant(0));
-    let mut exposed_air = flood_fill(&arr, [0, 0, 0]);
+/// Air that can't reach the outside, split into connected pockets, largest first
+fn air_pockets(arr: &Map, offset: Cube) -> Vec<Vec<Cube>> {
+    let mut map = flood_fill(arr, [0, 0, 0]);
+    let mut pockets = Vec::new();
+    for (x, y, z) in indices(map.dim()) {
+        let start = [x, y, z];
+        if map[start] == 0 {
+            let cells = fill(&mut map, start, 3);
+            pockets.push(
+                cells
+                    .into_iter()
+                    .map(|cell| [0, 1, 2].map(|axis| cell[axis] as i64 + offset[axis]))
+                    .sorted()
+                    .collect_vec(),
+            );
+        }
+    }
+    pockets.sort_by_key(|pocket| std::cmp::Reverse(pocket.len()));
+    pockets
+}
+
+/// `air_pockets` without a dense grid. Air against the lava that `sparse_outside` didn't reach
+/// is trapped, so filling from it stays inside the droplet. Running into outside air instead
+/// means `sparse_outside` missed some, which is an error rather than an endless fill.
+fn sparse_pockets(cubes: &[Cube]) -> Result<Vec<Vec<Cube>>> {
+    let lava: HashSet<Cube> = cubes.iter().copied().collect();
+    let outside = sparse_outside(&lava);
+    let mut seen = HashSet::new();
+    let mut pockets = Vec::new();
+    for start in lava
+        .iter()
+        .flat_map(|cube| STAR.map(|dir| step(*cube, dir)))
+    {
+        if lava.contains(&start) || outside.contains(&start) || !seen.insert(start) {
+            continue;
+        }
+        let mut pocket = vec![start];
+        let mut i = 0;
+        while i < pocket.len() {
+            for next in STAR.map(|dir| step(pocket[i], dir)) {
+                if outside.contains(&next) {
+                    bail!("Air at {next:?} is both trapped and outside");
+                }
+                if !lava.contains(&next) && seen.insert(next) {
+                    pocket.push(next);
+                }
+            }
+            i += 1;
+        }
+        pocket.sort_unstable();
+        pockets.push(pocket);
+    }
+    // the order `air_pockets` finds them in
+    pockets.sort_by_key(|pocket| (std::cmp::Reverse(pocket.len()), pocket[0]));
+    Ok(pockets)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Volumes {
+    lava: usize,
+    /// Size of each air pocket, largest first
+    pockets: Vec<usize>,
+}
+
+fn volumes(input: &str) -> Result<Volumes> {
+    let cubes = parse_cubes(input);
+    let pockets = match dense(&cubes) {
+        Some((arr, offset)) => air_pockets(&arr, offset),
+        None => sparse_pockets(&cubes)?,
+    };
+    Ok(Volumes {
+        lava: cubes.iter().unique().count(),
+        pockets: pockets.iter().map(Vec::len).collect(),
+    })
+}
+
+/// Unit squares with corners on the grid, wound counter-clockwise seen from outside
+#[derive(Debug, Default)]
+struct Mesh {
+    vertices: Vec<Cube>,
+    /// Indices into `vertices` and the outward normal
+    quads: Vec<([usize; 4], Cube)>,
+}
+
+impl Mesh {
+    /// The face of each cube on the side its normal points to
+    fn from_faces(faces: impl IntoIterator<Item = (Cube, Cube)>) -> Self {
+        let mut mesh = Mesh::default();
+        let mut index = HashMap::new();
+        for (cube, normal) in faces {
+            let corners = face(cube, normal).map(|corner| {
+                *index.entry(corner).or_insert_with(|| {
+                    mesh.vertices.push(corner);
+                    mesh.vertices.len() - 1
+                })
+            });
+            mesh.quads.push((corners, normal));
+        }
+        mesh
+    }
+
+    /// Every lava face that touches outside air
+    fn exterior(arr: &Map, offset: Cube) -> Self {
+        let outside = &flood_fill(arr, [0, 0, 0]);
+        let faces = arr
+            .indexed_iter()
+            .filter(|(_, v)| **v == 1)
+            .flat_map(|((x, y, z), _)| {
+                let cube = [
+                    x as i64 + offset[0],
+                    y as i64 + offset[1],
+                    z as i64 + offset[2],
+                ];
+                STAR.into_iter()
+                    .filter(move |dir| {
+                        let side = [
+                            x.wrapping_add(dir[0]),
+                            y.wrapping_add(dir[1]),
+                            z.wrapping_add(dir[2]),
+                        ];
+                        outside.get(side) == Some(&2)
+                    })
+                    .map(move |dir| (cube, step([0; 3], dir)))
+            });
+        Mesh::from_faces(faces)
+    }
+
+    /// `exterior` without a dense grid, see `sparse_outside`. The cubes go in the same order
+    /// as in the grid, so both give the same mesh.
+    fn sparse_exterior(cubes: &[Cube]) -> Self {
+        let lava: HashSet<Cube> = cubes.iter().copied().collect();
+        let outside = sparse_outside(&lava);
+        let faces = lava.iter().sorted().flat_map(|cube| {
+            STAR.into_iter()
+                .filter(|dir| outside.contains(&step(*cube, *dir)))
+                .map(|dir| (*cube, step([0; 3], dir)))
+        });
+        Mesh::from_faces(faces)
+    }
+
+    fn to_obj(&self) -> String {
+        let mut obj = String::new();
+        for [x, y, z] in &self.vertices {
+            obj += &format!("v {x} {y} {z}\n");
+        }
+        for (corners, _) in &self.quads {
+            obj += &format!("f {}\n", corners.iter().map(|i| i + 1).join(" "));
+        }
+        obj
+    }
+
+    fn to_stl(&self) -> String {
+        let mut stl = String::from("solid droplet\n");
+        for (corners, normal) in &self.quads {
+            for triangle in [[0, 1, 2], [0, 2, 3]] {
+                stl += &format!("facet normal {}\nouter loop\n", normal.iter().join(" "));
+                for i in triangle {
+                    let vertex = self.vertices[corners[i]];
+                    stl += &format!("vertex {}\n", vertex.iter().join(" "));
+                }
+                stl += "endloop\nendfacet\n";
+            }
+        }
+        stl + "endsolid droplet\n"
+    }
+}
+
+/// Corners of the side of `cube` facing `normal`, counter-clockwise seen from that side
+fn face(cube: Cube, normal: Cube) -> [Cube; 4] {
+    let axis = normal.iter().position(|v| *v != 0).unwrap();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut base = cube;
+    base[axis] += i64::from(normal[axis] > 0);
+    let corner = |du: i64, dv: i64| {
+        let mut corner = base;
+        corner[u] += du;
+        corner[v] += dv;
+        corner
+    };
+    let corners = [corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)];
+    if normal[axis] > 0 {
+        corners
+    } else {
+        [corners[0], corners[3], corners[2], corners[1]]
+    }
+}
+
+fn exterior(arr: &Map) -> usize {
+    let mut exposed_air = flood_fill(arr, [0, 0, 0]);
     exposed_air.mapv_inplace(|v| if v == 0 { 1 } else { v });
     exposed_air.mapv_inplace(|v| if v == 2 { 0 } else { v });
 
@@ -114,17 +448,72 @@ fn part2(input: &str) -> usize {
         .sum::<usize>()
 }
 
-pub fn main() -> std::io::Result<()> {
+fn part1(input: &str) -> usize {
+    let cubes = parse_cubes(input);
+    match dense(&cubes) {
+        Some((arr, _)) => surface(&arr),
+        None => sparse_surface(&cubes),
+    }
+}
+
+fn part2(input: &str) -> usize {
+    let cubes = parse_cubes(input);
+    match dense(&cubes) {
+        Some((arr, _)) => exterior(&arr),
+        None => sparse_exterior(&cubes),
+    }
+}
+
+/// Slices through the droplet along z, showing lava, outside air and trapped air, and the outer
+/// surface as OBJ and STL meshes. Droplets too big for a dense grid only get the meshes.
+pub fn images(input: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let cubes = parse_cubes(input);
+    let mut images = Vec::new();
+    let mesh = match dense(&cubes) {
+        Some((arr, offset)) => {
+            let filled = flood_fill(&arr, [0, 0, 0]);
+            let frames = filled
+                .axis_iter(Axis(2))
+                .map(|slice| slice.to_owned())
+                .collect_vec();
+            let palette = Palette::new(
+                &[
+                    (0, [240, 200, 60]),
+                    (1, [200, 60, 30]),
+                    (2, [120, 170, 220]),
+                ],
+                [0, 0, 0],
+            );
+            images.push((
+                "slices.gif",
+                gif(&frames, &palette, 8, Duration::from_millis(200)),
+            ));
+            Mesh::exterior(&arr, offset)
+        }
+        None => Mesh::sparse_exterior(&cubes),
+    };
+    images.push(("surface.obj", mesh.to_obj().into_bytes()));
+    images.push(("surface.stl", mesh.to_stl().into_bytes()));
+    images
+}
+
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(18)?;
     dbg!(part1(input));
     dbg!(part2(input));
+    let volumes = volumes(input)?;
+    // lava, how many pockets of air it traps and how much air is in them
+    dbg!(
+        volumes.lava,
+        volumes.pockets.len(),
+        volumes.pockets.iter().sum::<usize>()
+    );
 
     Ok(())
 }
 
-#[test]
-fn example() {
-    let input = "2,2,2
+#[cfg(test)]
+const EXAMPLE: &str = "2,2,2
 1,2,2
 3,2,2
 2,1,2
@@ -137,8 +526,177 @@ fn example() {
 3,2,5
 2,1,5
 2,3,5";
-    assert_eq!(part1(input), 64);
-    assert_eq!(part2(input), 58);
+
+#[test]
+fn example() {
+    assert_eq!(part1(EXAMPLE), 64);
+    assert_eq!(part2(EXAMPLE), 58);
+}
+
+#[cfg(test)]
+fn cubes_to_input(cubes: &[Cube]) -> String {
+    cubes.iter().map(|cube| cube.iter().join(",")).join("\n")
+}
+
+#[cfg(test)]
+fn shifted(cubes: &[Cube], by: Cube) -> Vec<Cube> {
+    cubes
+        .iter()
+        .map(|cube| [0, 1, 2].map(|axis| cube[axis] + by[axis]))
+        .collect()
+}
+
+#[test]
+fn shifted_and_spread() {
+    let example = parse_cubes(EXAMPLE);
+    for by in [[-2, -2, -2], [-100, 7, 0], [5, -30, 12]] {
+        let cubes = shifted(&example, by);
+        let (arr, offset) = dense(&cubes).unwrap();
+        assert_eq!(arr.dim(), (5, 5, 8));
+        assert_eq!(offset, [by[0], by[1], by[2]]);
+        assert_eq!(part1(&cubes_to_input(&cubes)), 64);
+        assert_eq!(part2(&cubes_to_input(&cubes)), 58);
+    }
+
+    // copies of the example far apart, one of them inside a hollow cube
+    let hollow = (-10..=10)
+        .cartesian_product(-10..=10)
+        .cartesian_product(-10..=10)
+        .map(|((x, y), z)| [x, y, z])
+        .filter(|cube| cube.iter().any(|v| i64::abs(*v) == 10))
+        .collect_vec();
+    let mut cubes = shifted(&hollow, [0, 0, 1 << 40]);
+    for by in [[0, 0, 0], [1 << 30, -(1 << 30), 0], [0, 0, 1 << 40]] {
+        cubes.extend(shifted(&example, by));
+    }
+    assert!(dense(&cubes).is_none());
+    let input = cubes_to_input(&cubes);
+    assert_eq!(part1(&input), 3 * 64 + 6 * 21 * 21 + 6 * 19 * 19);
+    assert_eq!(part2(&input), 2 * 58 + 6 * 21 * 21);
+    // no slices, but the surface is still small enough to export
+    let images = images(&input);
+    assert_eq!(
+        images.iter().map(|(name, _)| *name).collect_vec(),
+        ["surface.obj", "surface.stl"]
+    );
+    let mesh = Mesh::sparse_exterior(&cubes);
+    assert_eq!(mesh.quads.len(), 2 * 58 + 6 * 21 * 21);
+    check_mesh(&mesh, &cubes);
+    assert_eq!(images[0].1, mesh.to_obj().into_bytes());
+    assert_eq!(
+        volumes(&input).unwrap(),
+        Volumes {
+            lava: 3 * 13 + 21 * 21 * 21 - 19 * 19 * 19,
+            // the inside of the hollow cube around the example in it, and the example's own
+            pockets: vec![19 * 19 * 19 - 13 - 1, 1, 1, 1]
+        }
+    );
+}
+
+#[test]
+fn sparse_matches_dense() {
+    let mut rng = XorShift::new(49);
+    for _ in 0..200 {
+        let size = 2 + rng.below(6) as i64;
+        let cubes = (0..rng.below(120))
+            .map(|_| [0, 1, 2].map(|_| rng.below(size as usize) as i64 - size / 2))
+            .unique()
+            .collect_vec();
+        let (arr, offset) = dense(&cubes).unwrap();
+        assert_eq!(sparse_surface(&cubes), surface(&arr), "{cubes:?}");
+        assert_eq!(sparse_exterior(&cubes), exterior(&arr), "{cubes:?}");
+        assert_eq!(
+            sparse_pockets(&cubes).unwrap(),
+            air_pockets(&arr, offset),
+            "{cubes:?}"
+        );
+        assert_eq!(
+            Mesh::sparse_exterior(&cubes).to_obj(),
+            Mesh::exterior(&arr, offset).to_obj(),
+            "{cubes:?}"
+        );
+    }
+}
+
+#[test]
+fn example_images() {
+    let images = images(EXAMPLE);
+    assert_eq!(images[0].0, "slices.gif");
+    let ((width, height), frames) = decode_gif(&images[0].1);
+    // x and y from 1 to 3 and z from 1 to 6, with a layer of air all around
+    assert_eq!((width, height), (5 * 8, 5 * 8));
+    assert_eq!(frames.len(), 8);
+    let count = |index| frames.concat().iter().filter(|p| **p == index).count() / 64;
+    assert_eq!((count(1), count(2), count(3)), (1, 13, 5 * 5 * 8 - 14));
+}
+
+#[test]
+fn example_analysis() {
+    assert_eq!(
+        volumes(EXAMPLE).unwrap(),
+        Volumes {
+            lava: 13,
+            pockets: vec![1]
+        }
+    );
+    let (arr, offset) = dense(&parse_cubes(EXAMPLE)).unwrap();
+    assert_eq!(air_pockets(&arr, offset), [[[2, 2, 5]]]);
+
+    let mesh = Mesh::exterior(&arr, offset);
+    assert_eq!(mesh.quads.len(), 58);
+    check_mesh(&mesh, &parse_cubes(EXAMPLE));
+    let stl = mesh.to_stl();
+    assert_eq!(stl.matches("endfacet").count(), 2 * 58);
+    assert!(stl.starts_with("solid droplet\nfacet normal"));
+
+    let (arr, offset) = dense(&[[-1, 4, 0]]).unwrap();
+    let mesh = Mesh::exterior(&arr, offset);
+    check_mesh(&mesh, &[[-1, 4, 0]]);
+    assert_eq!(
+        mesh.to_obj(),
+        "v 0 4 0
+v 0 5 0
+v 0 5 1
+v 0 4 1
+v -1 4 0
+v -1 4 1
+v -1 5 1
+v -1 5 0
+f 1 2 3 4
+f 5 6 7 8
+f 8 7 3 2
+f 5 1 4 6
+f 6 4 3 7
+f 5 8 2 1
+"
+    );
+}
+
+/// Every quad is a unit square wound around its normal, with lava behind it and none in front
+#[cfg(test)]
+fn check_mesh(mesh: &Mesh, cubes: &[Cube]) {
+    let lava: HashSet<Cube> = cubes.iter().copied().collect();
+    for (corners, normal) in &mesh.quads {
+        let [a, b, c, d] = corners.map(|i| mesh.vertices[i]);
+        let (ab, ad) = (
+            [0, 1, 2].map(|i| b[i] - a[i]),
+            [0, 1, 2].map(|i| d[i] - a[i]),
+        );
+        let cross = [
+            ab[1] * ad[2] - ab[2] * ad[1],
+            ab[2] * ad[0] - ab[0] * ad[2],
+            ab[0] * ad[1] - ab[1] * ad[0],
+        ];
+        assert_eq!(cross, *normal);
+        assert_eq!([0, 1, 2].map(|i| a[i] + c[i] - b[i]), d);
+
+        // the cube behind the face has its lowest corner at the face's lowest corner, less the
+        // normal when it points the positive way
+        let low = [0, 1, 2].map(|i| a[i].min(b[i]).min(c[i]).min(d[i]));
+        let behind = [0, 1, 2].map(|i| low[i] - normal[i].max(0));
+        assert!(lava.contains(&behind));
+        assert!(!lava.contains(&[0, 1, 2].map(|i| behind[i] + normal[i])));
+    }
 }
 
 #[test]
@@ -146,6 +704,24 @@ fn task() {
     let input = &read_input_to_string(18).unwrap();
     assert_eq!(part1(input), 3326);
     assert_eq!(part2(input), 1996);
+
+    let cubes = parse_cubes(input);
+    let (arr, offset) = dense(&cubes).unwrap();
+    let mesh = Mesh::exterior(&arr, offset);
+    assert_eq!(mesh.quads.len(), 1996);
+    check_mesh(&mesh, &cubes);
+
+    // the faces not on the outside are exactly those against a pocket
+    let pockets: HashSet<Cube> = air_pockets(&arr, offset).into_iter().flatten().collect();
+    let inner = cubes
+        .iter()
+        .flat_map(|cube| STAR.map(|dir| step(*cube, dir)))
+        .filter(|side| pockets.contains(side))
+        .count();
+    assert_eq!(inner, 3326 - 1996);
+    let volumes = volumes(input).unwrap();
+    assert_eq!(volumes.lava, cubes.len());
+    assert_eq!(volumes.pockets.iter().sum::<usize>(), pockets.len());
 }
 
 #[bench]
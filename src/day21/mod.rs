@@ -1,37 +1,15 @@
 extern crate test;
 
-use std::{cmp::Ordering, collections::HashMap};
+use std::{cmp::Ordering, collections::HashMap, fmt::Write, ops::RangeInclusive};
 
+use anyhow::{anyhow, bail, ensure, Result};
 use itertools::Itertools;
 #[cfg(test)]
 use test::Bencher;
 
 use crate::utils::read_input_to_string;
 
-type MonkeyMap<'a> = HashMap<String, Operand<'a>>;
-
-#[derive(Debug, Clone, Copy)]
-enum Operand<'a> {
-    Num(i64),
-    Op(Operation<'a>),
-}
-
-impl<'a> Operand<'a> {
-    fn value(&self, monkey_map: &MonkeyMap) -> i64 {
-        match &self {
-            Self::Num(value) => *value,
-            Self::Op(operation) => operation.apply(monkey_map),
-        }
-    }
-}
-
-impl<'a> From<&str> for Operand<'a> {
-    fn from(s: &str) -> Self {
-        Operand::Num(s.parse().unwrap())
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Operator {
     Add,
     Mul,
@@ -39,134 +17,394 @@ enum Operator {
     Sub,
 }
 
-impl From<&str> for Operator {
-    fn from(s: &str) -> Self {
+impl TryFrom<&str> for Operator {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self> {
         match s {
-            "+" => Operator::Add,
-            "*" => Operator::Mul,
-            "/" => Operator::Div,
-            "-" => Operator::Sub,
-            _ => panic!("Unknown operator {s}"),
+            "+" => Ok(Operator::Add),
+            "*" => Ok(Operator::Mul),
+            "/" => Ok(Operator::Div),
+            "-" => Ok(Operator::Sub),
+            _ => bail!("Unknown operator {s}"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Operation<'a> {
-    lhs: &'a str,
-    rhs: &'a str,
-    op: Operator,
-}
+impl Operator {
+    fn symbol(&self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Mul => '*',
+            Operator::Div => '/',
+            Operator::Sub => '-',
+        }
+    }
 
-impl<'a> Operation<'a> {
-    fn apply(&self, monkey_map: &MonkeyMap) -> i64 {
-        let lhs = monkey_map[self.lhs].value(monkey_map);
-        let rhs = monkey_map[self.rhs].value(monkey_map);
-        match &self.op {
-            Operator::Add => lhs + rhs,
-            Operator::Mul => lhs * rhs,
-            Operator::Div => lhs / rhs,
-            Operator::Sub => lhs - rhs,
+    fn apply(&self, lhs: i64, rhs: i64) -> Result<i64> {
+        match self {
+            Operator::Add => lhs.checked_add(rhs),
+            Operator::Mul => lhs.checked_mul(rhs),
+            Operator::Div => lhs.checked_div(rhs),
+            Operator::Sub => lhs.checked_sub(rhs),
         }
+        .ok_or_else(|| anyhow!("{lhs} {} {rhs} overflows or divides by zero", self.symbol()))
     }
 }
 
-fn parse_input(input: &str, p2: bool) -> MonkeyMap {
-    input
-        .lines()
-        .map(|line| {
-            let (name, operand) = line.split_once(": ").unwrap();
-            let operand = if let Some((lhs, op, rhs)) = operand.split_whitespace().collect_tuple() {
-                if p2 && name == "root" {
-                    Operand::Op(Operation {
-                        lhs,
-                        rhs,
-                        op: Operator::Sub,
-                    })
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    Num(i64),
+    Op(usize, Operator, usize),
+}
+
+/// The monkeys compiled into a DAG, indexed in input order
+#[derive(Debug, Clone)]
+struct Expr {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    nodes: Vec<Node>,
+    /// Every node after the nodes it depends on
+    order: Vec<usize>,
+}
+
+impl Expr {
+    fn compile(input: &str) -> Result<Self> {
+        let lines: Vec<(&str, &str)> = input
+            .lines()
+            .map(|line| {
+                line.split_once(": ")
+                    .ok_or_else(|| anyhow!("Could not parse {line}"))
+            })
+            .try_collect()?;
+
+        let names = lines.iter().map(|(name, _)| name.to_string()).collect_vec();
+        let mut index = HashMap::new();
+        for (idx, name) in names.iter().enumerate() {
+            ensure!(
+                index.insert(name.clone(), idx).is_none(),
+                "Monkey {name} is defined twice"
+            );
+        }
+
+        let nodes = lines
+            .iter()
+            .map(|(name, job)| {
+                if let Some((lhs, op, rhs)) = job.split_whitespace().collect_tuple() {
+                    let lookup = |other: &str| {
+                        index.get(other).copied().ok_or_else(|| {
+                            anyhow!("Monkey {name} waits for unknown monkey {other}")
+                        })
+                    };
+                    Ok(Node::Op(lookup(lhs)?, op.try_into()?, lookup(rhs)?))
                 } else {
-                    Operand::Op(Operation {
-                        lhs,
-                        rhs,
-                        op: op.into(),
-                    })
+                    Ok(Node::Num(job.parse()?))
                 }
-            } else {
-                Operand::Num(operand.parse().unwrap())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut expr = Expr {
+            names,
+            index,
+            nodes,
+            order: Vec::new(),
+        };
+        expr.order = expr.topological_order()?;
+        Ok(expr)
+    }
+
+    fn topological_order(&self) -> Result<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Mark {
+            New,
+            Open,
+            Done,
+        }
+
+        let mut marks = vec![Mark::New; self.nodes.len()];
+        let mut order = Vec::with_capacity(self.nodes.len());
+        for start in 0..self.nodes.len() {
+            // (node, children visited), explicit stack as chains can be long
+            let mut stack = vec![(start, false)];
+            while let Some((idx, expanded)) = stack.pop() {
+                if expanded {
+                    marks[idx] = Mark::Done;
+                    order.push(idx);
+                    continue;
+                }
+                match marks[idx] {
+                    Mark::Done => continue,
+                    Mark::Open => bail!("Monkey {} waits for itself", self.names[idx]),
+                    Mark::New => (),
+                }
+                marks[idx] = Mark::Open;
+                stack.push((idx, true));
+                if let Node::Op(lhs, _, rhs) = self.nodes[idx] {
+                    for child in [lhs, rhs] {
+                        match marks[child] {
+                            Mark::Open => bail!(
+                                "Monkeys {} and {} wait for each other",
+                                self.names[idx],
+                                self.names[child]
+                            ),
+                            Mark::New => stack.push((child, false)),
+                            Mark::Done => (),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    fn idx(&self, name: &str) -> Result<usize> {
+        self.index
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown monkey {name}"))
+    }
+
+    /// Value of every node, each shared subtree is computed once
+    fn evaluate(&self) -> Result<Vec<i64>> {
+        self.evaluate_except(&vec![false; self.nodes.len()])
+    }
+
+    /// Like `evaluate`, but nodes where `skip` is set are left at 0 and never computed
+    fn evaluate_except(&self, skip: &[bool]) -> Result<Vec<i64>> {
+        let mut values = vec![0; self.nodes.len()];
+        for &idx in self.order.iter().filter(|idx| !skip[**idx]) {
+            values[idx] = match self.nodes[idx] {
+                Node::Num(value) => value,
+                Node::Op(lhs, op, rhs) => op.apply(values[lhs], values[rhs])?,
             };
-            (name.to_owned(), operand)
-        })
-        .collect()
-}
+        }
+        Ok(values)
+    }
 
-fn part1(input: &str) -> usize {
-    let map = parse_input(input, false);
-    let root_op = &map["root"];
-    root_op.value(&map) as usize
-}
+    fn value(&self, name: &str) -> Result<i64> {
+        Ok(self.evaluate()?[self.idx(name)?])
+    }
 
-fn get_diff(insert_value: i64, map: &MonkeyMap) -> i64 {
-    let mut map = map.to_owned();
-    map.insert("humn".to_owned(), Operand::Num(insert_value));
-    let root_op = &map["root"];
-    root_op.value(&map)
-}
+    /// Whether each node depends on `target`
+    fn depends_on(&self, target: usize) -> Vec<bool> {
+        let mut depends = vec![false; self.nodes.len()];
+        for &idx in &self.order {
+            depends[idx] = idx == target
+                || match self.nodes[idx] {
+                    Node::Num(_) => false,
+                    Node::Op(lhs, _, rhs) => depends[lhs] || depends[rhs],
+                };
+        }
+        depends
+    }
+
+    fn write_formula(&self, idx: usize, symbols: &[usize], out: &mut String) {
+        match self.nodes[idx] {
+            _ if symbols.contains(&idx) => out.push_str(&self.names[idx]),
+            Node::Num(value) => write!(out, "{value}").unwrap(),
+            Node::Op(lhs, op, rhs) => {
+                out.push('(');
+                self.write_formula(lhs, symbols, out);
+                write!(out, " {} ", op.symbol()).unwrap();
+                self.write_formula(rhs, symbols, out);
+                out.push(')');
+            }
+        }
+    }
 
-fn signum(v: i64) -> i64 {
-    match v.cmp(&0) {
-        Ordering::Equal => 0,
-        Ordering::Greater => 1,
-        Ordering::Less => -1,
+    /// Infix formula for monkey `name`, keeping the monkeys in `symbols` as variables
+    fn formula(&self, name: &str, symbols: &[&str]) -> Result<String> {
+        let symbols: Vec<usize> = symbols.iter().map(|v| self.idx(v)).try_collect()?;
+        let mut out = String::new();
+        self.write_formula(self.idx(name)?, &symbols, &mut out);
+        Ok(out)
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let name = &self.names[idx];
+            match node {
+                Node::Num(value) => writeln!(out, "    {name} [label=\"{name}\\n{value}\"];"),
+                Node::Op(lhs, op, rhs) => writeln!(
+                    out,
+                    "    {name} [label=\"{name}\\n{}\"];\n    {name} -> {};\n    {name} -> {};",
+                    op.symbol(),
+                    self.names[*lhs],
+                    self.names[*rhs]
+                ),
+            }
+            .unwrap();
+        }
+        out.push('}');
+        out
     }
 }
 
-fn next_try(lower_try: i64, upper_try: i64, map: &MonkeyMap) -> (i64, i64) {
-    let lower_value = get_diff(lower_try, map);
-    let upper_value = get_diff(upper_try, map);
+fn part1(input: &str) -> Result<i64> {
+    Expr::compile(input)?.value("root")
+}
 
-    let lower_signum = signum(lower_value);
-    let upper_signum = signum(upper_value);
-    assert!(upper_signum != lower_signum);
+fn floor_div(a: i128, b: i128) -> i128 {
+    a.div_euclid(b)
+}
 
-    let prev_try_diff = upper_try - lower_try;
-    let mid_try = lower_try + prev_try_diff / 2;
-    let mid = get_diff(mid_try, map);
-    let mid_signum = signum(mid);
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -(-a).div_euclid(b)
+}
 
-    if mid_signum == lower_signum {
-        (mid_try, upper_try)
-    } else if mid_signum == upper_signum {
-        (lower_try, mid_try)
-    } else {
-        (mid_try, mid_try)
+/// All `x` with `x * c` in `lo..=hi`
+fn invert_mul((lo, hi): (i128, i128), c: i128) -> Result<(i128, i128)> {
+    match c.cmp(&0) {
+        Ordering::Greater => Ok((ceil_div(lo, c), floor_div(hi, c))),
+        Ordering::Less => Ok((ceil_div(-hi, -c), floor_div(-lo, -c))),
+        Ordering::Equal => bail!("humn is multiplied by zero"),
     }
 }
 
-fn part2(input: &str, minus_one: bool) -> i64 {
-    let map = parse_input(input, true);
+/// All `x` with `x / c` (rounding towards zero) in `lo..=hi`
+fn invert_div((lo, hi): (i128, i128), c: i128) -> Result<(i128, i128)> {
+    match c.cmp(&0) {
+        Ordering::Greater => {
+            let min = if lo > 0 { lo * c } else { lo * c - (c - 1) };
+            let max = if hi >= 0 { hi * c + (c - 1) } else { hi * c };
+            Ok((min, max))
+        }
+        Ordering::Less => invert_div((lo, hi), -c).map(|(min, max)| (-max, -min)),
+        Ordering::Equal => bail!("Division by zero"),
+    }
+}
 
-    let mut lower_try = 0;
-    let mut upper_try = 100;
+/// Compiles the input with root comparing its two operands, `root == 0` when they are equal
+fn compile_p2(input: &str) -> Result<Expr> {
+    let mut expr = Expr::compile(input)?;
+    let root = expr.idx("root")?;
+    if let Node::Op(lhs, _, rhs) = expr.nodes[root] {
+        expr.nodes[root] = Node::Op(lhs, Operator::Sub, rhs);
+    }
+    Ok(expr)
+}
 
-    while signum(get_diff(lower_try, &map)) == signum(get_diff(upper_try, &map)) {
-        lower_try = upper_try;
-        upper_try *= 10;
+/// One operation on the way from root to `humn`
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    op: Operator,
+    /// Value of the operand that does not depend on `humn`
+    c: i128,
+    /// Whether `humn` is in the left operand
+    humn_left: bool,
+}
+
+/// The operations from root down to `humn`. Only the operands that don't depend on `humn` are
+/// evaluated, so whatever `humn` holds in the input can't make this fail.
+fn humn_path(expr: &Expr) -> Result<Vec<Step>> {
+    let humn = expr.idx("humn")?;
+    let depends = expr.depends_on(humn);
+    let values = expr.evaluate_except(&depends)?;
+    let mut path = Vec::new();
+    let mut idx = expr.idx("root")?;
+    while idx != humn {
+        let name = &expr.names[idx];
+        let (lhs, op, rhs) = match expr.nodes[idx] {
+            Node::Op(lhs, op, rhs) => (lhs, op, rhs),
+            Node::Num(_) => bail!("{name} does not depend on humn"),
+        };
+        idx = match (depends[lhs], depends[rhs]) {
+            (true, true) => bail!("humn appears on both sides of {name}"),
+            (false, false) => bail!("{name} does not depend on humn"),
+            (false, true) if op == Operator::Div => {
+                bail!("humn is in the denominator of {name}, which is not linear")
+            }
+            (humn_left, _) => {
+                let (next, other) = if humn_left { (lhs, rhs) } else { (rhs, lhs) };
+                path.push(Step {
+                    op,
+                    c: values[other] as i128,
+                    humn_left,
+                });
+                next
+            }
+        };
     }
+    Ok(path)
+}
 
-    while lower_try != upper_try {
-        (lower_try, upper_try) = next_try(lower_try, upper_try, &map);
+/// Narrows `target`, the allowed values of root, down to the allowed values of `humn`
+/// by inverting each operation on the path from root to `humn`
+fn solve_for_humn(expr: &Expr, mut target: (i128, i128)) -> Result<(i128, i128)> {
+    for Step { op, c, humn_left } in humn_path(expr)? {
+        let (lo, hi) = target;
+        target = match op {
+            Operator::Add => (lo - c, hi - c),
+            Operator::Sub if humn_left => (lo + c, hi + c),
+            Operator::Sub => (c - hi, c - lo),
+            Operator::Mul => invert_mul(target, c)?,
+            Operator::Div => invert_div(target, c)?,
+        };
+        if target.0 > target.1 {
+            bail!("No value of humn makes root match");
+        }
     }
+    Ok(target)
+}
 
-    if minus_one {
-        lower_try - 1
-    } else {
-        lower_try
+/// The single value of `humn` that makes root equal `target` with every division on the way
+/// exact, which makes each inversion give exactly one value
+fn solve_exact(expr: &Expr, mut target: i128) -> Result<i128> {
+    for Step { op, c, humn_left } in humn_path(expr)? {
+        target = match op {
+            Operator::Add => target - c,
+            Operator::Sub if humn_left => target + c,
+            Operator::Sub => c - target,
+            Operator::Mul => {
+                ensure!(c != 0, "humn is multiplied by zero");
+                ensure!(target % c == 0, "No value of humn makes root match exactly");
+                target / c
+            }
+            Operator::Div => target * c,
+        };
     }
+    Ok(target)
 }
 
-pub fn main() -> std::io::Result<()> {
+/// Every value of humn that makes both sides of root equal
+fn humn_values(input: &str) -> Result<RangeInclusive<i64>> {
+    let (lo, hi) = solve_for_humn(&compile_p2(input)?, (0, 0))?;
+    Ok(lo.try_into()?..=hi.try_into()?)
+}
+
+/// Integer division makes several values valid, the intended one divides exactly
+fn part2(input: &str) -> Result<i64> {
+    let mut expr = compile_p2(input)?;
+    let candidate = solve_exact(&expr, 0)?.try_into()?;
+
+    // divisions off the path to humn have to be exact too
+    let humn = expr.idx("humn")?;
+    expr.nodes[humn] = Node::Num(candidate);
+    let values = expr.evaluate()?;
+    let exact = expr.nodes.iter().all(|node| match *node {
+        Node::Op(lhs, Operator::Div, rhs) => values[lhs] % values[rhs] == 0,
+        _ => true,
+    });
+    ensure!(exact, "No value of humn divides exactly");
+    Ok(candidate)
+}
+
+/// The monkeys as a graph, and root as a formula in humn
+pub fn exports(input: &str) -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let expr = Expr::compile(input)?;
+    let formula = expr.formula("root", &["humn"])? + "\n";
+    Ok(vec![
+        ("monkeys.dot", expr.to_dot().into_bytes()),
+        ("root.txt", formula.into_bytes()),
+    ])
+}
+
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(21)?;
-    dbg!(part1(input));
-    dbg!(part2(input, true));
+    dbg!(part1(input)?);
+    dbg!(part2(input)?);
+    dbg!(humn_values(input)?);
 
     Ok(())
 }
@@ -188,22 +426,152 @@ pppw: cczh / lfqf
 lgvd: ljgn * ptdq
 drzm: hmdt - zczc
 hmdt: 32";
-    assert_eq!(part1(input), 152);
-    assert_eq!(part2(input, false), 301);
+    assert_eq!(part1(input).unwrap(), 152);
+    assert_eq!(part2(input).unwrap(), 301);
+    assert_eq!(humn_values(input).unwrap(), 301..=302);
+}
+
+#[test]
+fn humn_edge_cases() {
+    let both_sides = "root: humn + aaaa
+aaaa: humn * bbbb
+bbbb: 2
+humn: 1";
+    assert!(humn_values(both_sides).is_err());
+    let denominator = "root: aaaa + bbbb
+aaaa: bbbb / humn
+bbbb: 2
+humn: 1";
+    assert!(humn_values(denominator).is_err());
+    let doubled = "root: aaaa + bbbb
+aaaa: humn * bbbb
+bbbb: 2
+humn: 1";
+    assert_eq!(humn_values(doubled).unwrap(), 1..=1);
+    let odd = "root: aaaa + cccc
+aaaa: humn * bbbb
+bbbb: 2
+cccc: 3
+humn: 1";
+    assert!(humn_values(odd).is_err());
+    let reciprocal = "root: aaaa + cccc
+aaaa: dddd / humn
+cccc: 3
+dddd: 7
+humn: 1";
+    assert!(humn_values(reciprocal).is_err());
+    let truncating = "root: aaaa + cccc
+aaaa: humn / bbbb
+bbbb: -4
+cccc: 1
+humn: 1";
+    assert_eq!(humn_values(truncating).unwrap(), -7..=-4);
+    let overflowing = "root: aaaa + cccc
+aaaa: humn * bbbb
+bbbb: 3
+cccc: 6
+humn: 9223372036854775807";
+    assert!(Expr::compile(overflowing).unwrap().value("root").is_err());
+    assert_eq!(humn_values(overflowing).unwrap(), 2..=2);
+    assert_eq!(part2(truncating).unwrap(), -4);
+    assert!(part2(odd).is_err());
+
+    // the input's humn would overflow, and a wide range of values is valid when truncating
+    let huge = "root: aaaa + cccc
+aaaa: bbbb * dddd
+bbbb: humn / eeee
+dddd: 3
+eeee: 100000000000
+cccc: 6
+humn: 9223372036854775807";
+    assert_eq!(
+        humn_values(huge).unwrap(),
+        200_000_000_000..=299_999_999_999
+    );
+    assert_eq!(part2(huge).unwrap(), 200_000_000_000);
+    let zero = "root: aaaa + cccc
+aaaa: humn / bbbb
+bbbb: 0
+cccc: 1
+humn: 1";
+    assert!(humn_values(zero).is_err());
+}
+
+#[test]
+fn compile_errors() {
+    assert!(Expr::compile("root: aaaa + bbbb\naaaa: 1").is_err());
+    assert!(Expr::compile("root: aaaa + aaaa\naaaa: 1\naaaa: 2").is_err());
+    assert!(Expr::compile("root: aaaa % aaaa\naaaa: 1").is_err());
+    assert!(Expr::compile("root: root + aaaa\naaaa: 1").is_err());
+    assert!(
+        Expr::compile("root: aaaa + bbbb\naaaa: bbbb * cccc\nbbbb: 1\ncccc: root - bbbb").is_err()
+    );
+    let overflow = Expr::compile("root: aaaa * aaaa\naaaa: 9999999999").unwrap();
+    assert!(overflow.value("root").is_err());
+    let zero = Expr::compile("root: aaaa / bbbb\naaaa: 1\nbbbb: 0").unwrap();
+    assert!(zero.value("root").is_err());
+}
+
+#[test]
+fn example_export() {
+    let input = "root: pppw + sjmn
+dbpl: 5
+cczh: sllz + lgvd
+zczc: 2
+ptdq: humn - dvpt
+dvpt: 3
+lfqf: 4
+humn: 5
+ljgn: 2
+sjmn: drzm * dbpl
+sllz: 4
+pppw: cczh / lfqf
+lgvd: ljgn * ptdq
+drzm: hmdt - zczc
+hmdt: 32";
+    let expr = Expr::compile(input).unwrap();
+    assert_eq!(
+        expr.formula("root", &["humn"]).unwrap(),
+        "(((4 + (2 * (humn - 3))) / 4) + ((32 - 2) * 5))"
+    );
+    assert_eq!(expr.formula("pppw", &["cczh"]).unwrap(), "(cczh / 4)");
+    assert_eq!(
+        Expr::compile("root: aaaa * bbbb\naaaa: 3\nbbbb: 4")
+            .unwrap()
+            .to_dot(),
+        "digraph {
+    root [label=\"root\\n*\"];
+    root -> aaaa;
+    root -> bbbb;
+    aaaa [label=\"aaaa\\n3\"];
+    bbbb [label=\"bbbb\\n4\"];
+}"
+    );
+}
+
+#[test]
+fn example_exports() {
+    let exports = exports("root: aaaa * humn\naaaa: 3\nhumn: 4").unwrap();
+    assert_eq!(exports[0].0, "monkeys.dot");
+    assert!(String::from_utf8(exports[0].1.clone())
+        .unwrap()
+        .contains("root -> humn;"));
+    assert_eq!(exports[1], ("root.txt", b"(3 * humn)\n".to_vec()));
 }
 
 #[test]
 fn task() {
     let input = &read_input_to_string(21).unwrap();
-    assert_eq!(part1(input), 223971851179174);
-    assert_eq!(part2(input, true), 3379022190351);
+    assert_eq!(part1(input).unwrap(), 223971851179174);
+    assert_eq!(part2(input).unwrap(), 3379022190351);
+    assert_eq!(humn_values(input).unwrap(), 3379022190351..=3379022190352);
 }
 
 #[bench]
 fn task_bench(b: &mut Bencher) {
     b.iter(|| {
         let input = &read_input_to_string(21).unwrap();
-        part1(input);
-        part2(input, true);
+        part1(input).unwrap();
+        part2(input).unwrap();
     })
 }
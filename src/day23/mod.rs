@@ -3,6 +3,7 @@ extern crate test;
 use std::{
     collections::HashSet,
     ops::{Add, AddAssign, Sub},
+    time::Duration,
 };
 
 use itertools::Itertools;
@@ -10,7 +11,13 @@ use ndarray::Array2;
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::{pretty_print, read_input_to_string};
+#[cfg(test)]
+use crate::export::decode_gif;
+use crate::{
+    animate::{Frame, Recorder},
+    export::{gif, Palette},
+    utils::{pretty_print, read_input_to_string},
+};
 
 fn parse_input(input: &str) -> Vec<Position> {
     let mut elves = Vec::new();
@@ -197,14 +204,19 @@ fn min_max_per_axis<'a>(positions: impl Iterator<Item = &'a Position>) -> (Posit
     )
 }
 
-fn visualize(elves: &[Position]) -> String {
-    let (mn, mx) = min_max_per_axis(elves.iter());
+/// Elves inside the rectangle from `mn` to `mx`
+fn grid(elves: &[Position], mn: Position, mx: Position) -> Array2<char> {
     let shape = mx - mn + Position::new(1, 1);
     let mut arr: Array2<char> = Array2::<char>::from_elem(shape.to_index(), '.');
     for pos in elves {
         arr[(*pos - mn).to_index()] = '#';
     }
-    pretty_print(&arr)
+    arr
+}
+
+fn visualize(elves: &[Position]) -> String {
+    let (mn, mx) = min_max_per_axis(elves.iter());
+    pretty_print(&grid(elves, mn, mx))
 }
 
 #[allow(dead_code)]
@@ -212,7 +224,11 @@ fn visualize_print(elves: &[Position]) {
     println!("{}", visualize(elves));
 }
 
-fn parts(input: &str, max_rounds: Option<usize>) -> (Vec<Position>, usize) {
+fn parts(
+    input: &str,
+    max_rounds: Option<usize>,
+    mut on_round: impl FnMut(&[Position]),
+) -> (Vec<Position>, usize) {
     let mut check_order = [
         Direction::North,
         Direction::South,
@@ -254,6 +270,7 @@ fn parts(input: &str, max_rounds: Option<usize>) -> (Vec<Position>, usize) {
         proposed_positions.clear();
         duplicated.clear();
         round += 1;
+        on_round(&elves);
         if let Some(mx) = max_rounds {
             if round >= mx {
                 break;
@@ -264,14 +281,45 @@ fn parts(input: &str, max_rounds: Option<usize>) -> (Vec<Position>, usize) {
 }
 
 fn part1(input: &str) -> usize {
-    let (elves, _) = parts(input, Some(10));
+    let (elves, _) = parts(input, Some(10), |_| ());
     let (mn, mx) = min_max_per_axis(elves.iter());
     let rectangle = mx - mn + Position::new(1, 1);
     (rectangle.x * rectangle.y) as usize - elves.len()
 }
 
 fn part2(input: &str) -> usize {
-    parts(input, None).1
+    parts(input, None, |_| ()).1
+}
+
+/// Every round of part 2 until the elves stop moving
+pub fn animate(input: &str) -> Vec<Frame> {
+    let mut recorder = Recorder::new();
+    recorder.record(|| Frame::new(visualize(&parse_input(input))));
+    parts(input, None, |elves| {
+        recorder.record(|| Frame::new(visualize(elves)))
+    });
+    recorder.into_frames()
+}
+
+/// The elves spreading out, every twentieth round of part 2 on a fixed canvas
+pub fn images(input: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let mut rounds = vec![parse_input(input)];
+    let mut round = 0;
+    let (last, _) = parts(input, None, |elves| {
+        round += 1;
+        if round % 20 == 0 {
+            rounds.push(elves.to_vec());
+        }
+    });
+    rounds.push(last);
+
+    let (mn, mx) = min_max_per_axis(rounds.iter().flatten());
+    let frames = rounds.iter().map(|elves| grid(elves, mn, mx)).collect_vec();
+    let palette = Palette::new(&[('#', [90, 200, 90])], [15, 25, 15]);
+    vec![(
+        "spreading.gif",
+        gif(&frames, &palette, 2, Duration::from_millis(50)),
+    )]
 }
 
 pub fn main() -> std::io::Result<()> {
@@ -292,6 +340,11 @@ fn tiny_example() {
 .....";
     assert_eq!(part1(input), 25);
     assert_eq!(part2(input), 4);
+
+    let frames = animate(input);
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[0].text, "##\n#.\n..\n##");
+    assert_eq!(frames[4].text, "..#..\n....#\n#....\n....#\n.....\n..#..");
 }
 
 #[test]
@@ -307,6 +360,30 @@ fn example() {
     assert_eq!(part2(input), 20);
 }
 
+#[test]
+fn example_images() {
+    let input = "....#..
+..###.#
+#...#.#
+.#...##
+#.###..
+##.#.##
+.#..#..";
+    let images = images(input);
+    assert_eq!(images[0].0, "spreading.gif");
+    let ((width, height), frames) = decode_gif(&images[0].1);
+    // the start, round 20 and where they stop, which is round 20 again
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[1], frames[2]);
+    let (last, _) = parts(input, None, |_| ());
+    let (mn, mx) = min_max_per_axis(parse_input(input).iter().chain(&last));
+    let cells = (mx - mn + Position::new(1, 1)).to_index();
+    assert_eq!((width, height), (cells[1] * 2, cells[0] * 2));
+    for frame in &frames {
+        assert_eq!(frame.iter().filter(|p| **p == 1).count(), 22 * 4);
+    }
+}
+
 #[test]
 fn task() {
     let input = &read_input_to_string(23).unwrap();
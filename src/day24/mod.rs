@@ -10,7 +10,10 @@ use ndarray::{s, Array2, Dim};
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::{pretty_print, read_input_to_string};
+use crate::{
+    animate::{Frame, Recorder},
+    utils::{pretty_print, read_input_to_string},
+};
 
 type Board = Array2<char>;
 
@@ -160,7 +163,6 @@ impl AddAssign for Position {
     }
 }
 
-#[allow(dead_code)]
 fn visualize(
     board: &Board,
     blizzards: &[(Position, Direction)],
@@ -264,11 +266,14 @@ fn pad(arr: &Board, value: char) -> Board {
     board
 }
 
+/// Minutes to get from `start` to `goal`, calling `on_minute` with the blizzards and the
+/// position closest to the goal after each one
 fn pathfind(
     start: &Position,
     goal: &Position,
     blizzards: &mut [(Position, Direction)],
     board: &Board,
+    on_minute: &mut impl FnMut(&[(Position, Direction)], &Position),
 ) -> usize {
     let mut states = vec![State {
         position: *start,
@@ -290,8 +295,7 @@ fn pathfind(
 
         next_states.sort_by(|a, b| a.position.manhattan(goal).cmp(&b.position.manhattan(goal)));
 
-        // visualize_print(&board, &blizzards, Some(&next_states[0].position), None);
-        // println!();
+        on_minute(blizzards, &next_states[0].position);
 
         next_states = next_states.into_iter().take(100).collect_vec();
 
@@ -307,9 +311,7 @@ fn part1(input: &str) -> usize {
     let start = Position::new(2, 1);
     let goal = Position::new(board.dim().1 - 3, board.dim().0 - 2);
 
-    // visualize_print(&board, &blizzards, Some(&start), Some(&goal));
-
-    pathfind(&start, &goal, &mut blizzards, &board)
+    pathfind(&start, &goal, &mut blizzards, &board, &mut |_, _| ())
 }
 
 fn part2(input: &str) -> usize {
@@ -319,11 +321,39 @@ fn part2(input: &str) -> usize {
     let start = Position::new(2, 1);
     let goal = Position::new(board.dim().1 - 3, board.dim().0 - 2);
 
-    // visualize_print(&board, &blizzards, Some(&start), Some(&goal));
+    let mut on_minute = |_: &[(Position, Direction)], _: &Position| ();
+    pathfind(&start, &goal, &mut blizzards, &board, &mut on_minute)
+        + pathfind(&goal, &start, &mut blizzards, &board, &mut on_minute)
+        + pathfind(&start, &goal, &mut blizzards, &board, &mut on_minute)
+}
 
-    pathfind(&start, &goal, &mut blizzards, &board)
-        + pathfind(&goal, &start, &mut blizzards, &board)
-        + pathfind(&start, &goal, &mut blizzards, &board)
+/// The expedition's trip there, back and there again, minute by minute
+pub fn animate(input: &str) -> Vec<Frame> {
+    let board = parse_board(input);
+    let board = pad(&board, '#');
+    let mut blizzards = parse_blizzards(input);
+    let start = Position::new(2, 1);
+    let goal = Position::new(board.dim().1 - 3, board.dim().0 - 2);
+
+    let mut recorder = Recorder::new();
+    for (from, to) in [(start, goal), (goal, start), (start, goal)] {
+        recorder.record(|| {
+            Frame::focused(
+                visualize(&board, &blizzards, Some(&from), Some(&to)),
+                (from.y, from.x),
+            )
+        });
+        let mut on_minute = |blizzards: &[(Position, Direction)], expedition: &Position| {
+            recorder.record(|| {
+                Frame::focused(
+                    visualize(&board, blizzards, Some(expedition), Some(&to)),
+                    (expedition.y, expedition.x),
+                )
+            })
+        };
+        pathfind(&from, &to, &mut blizzards, &board, &mut on_minute);
+    }
+    recorder.into_frames()
 }
 
 pub fn main() -> std::io::Result<()> {
@@ -376,6 +406,12 @@ fn example() {
         .trim();
     assert_eq!(part1(input), 18);
     assert_eq!(part2(input), 54);
+
+    // a frame before each of the three trips and one per minute
+    let frames = animate(input);
+    assert_eq!(frames.len(), 54 + 3);
+    assert_eq!(frames[0].focus, Some((1, 2)));
+    assert_eq!(frames.last().unwrap().focus, Some((6, 7)));
 }
 
 #[test]
@@ -1,82 +1,213 @@
 extern crate test;
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, fmt, str::FromStr};
 
-use lazy_static::lazy_static;
-use regex::Regex;
+use anyhow::{anyhow, bail, Result};
+use itertools::Itertools;
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::read_input_to_string;
+#[cfg(test)]
+use crate::utils::XorShift;
+use crate::{animate::Frame, utils::read_input_to_string};
 
 fn parse_stacks<'a>(
-    input_lines: &mut (impl Iterator<Item = &'a str> + std::clone::Clone),
-) -> Vec<VecDeque<char>> {
-    let n_stacks = (input_lines.clone().next().unwrap().len() + 1) / 4;
+    input_lines: &mut impl Iterator<Item = &'a str>,
+) -> Result<Vec<VecDeque<char>>> {
+    let mut drawing = input_lines
+        .take_while(|line| !line.trim().is_empty())
+        .collect_vec();
+    let footer = drawing
+        .last()
+        .filter(|line| line.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+        .copied();
+    let n_stacks = if let Some(footer) = footer {
+        drawing.pop();
+        footer.split_whitespace().count()
+    } else {
+        drawing
+            .iter()
+            .map(|line| (line.len() + 1) / 4)
+            .max()
+            .unwrap_or(0)
+    };
     let mut stacks: Vec<VecDeque<char>> = vec![VecDeque::new(); n_stacks];
-    input_lines
-        .take_while(|line| !line.is_empty())
-        .for_each(|line| {
-            let mut char_iter = line.chars();
-            char_iter.advance_by(1).unwrap();
-            char_iter.step_by(4).enumerate().for_each(|(idx, c)| {
-                if c != ' ' {
-                    stacks[idx].push_front(c)
-                }
-            });
-        });
-    stacks
-}
-
-fn get_integers(text: &str) -> Vec<usize> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"-?\d+").unwrap();
+    for line in drawing.into_iter().rev() {
+        for (idx, c) in line.chars().skip(1).step_by(4).enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            stacks
+                .get_mut(idx)
+                .ok_or_else(|| anyhow!("Crate {c} is outside of the {n_stacks} stacks"))?
+                .push_back(c);
+        }
     }
-    RE.captures_iter(text)
-        .map(|m| m[0].parse())
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap()
-}
-
-fn do_one_move(mv: Vec<usize>, stacks: &mut [VecDeque<char>], rev: bool) {
-    let from_stack = &mut stacks[mv[1] - 1];
-    let crates = from_stack.split_off(from_stack.len() - mv[0]);
-    if rev {
-        crates
-            .into_iter()
-            .rev()
-            .for_each(|v| stacks[mv[2] - 1].push_back(v))
-    } else {
-        crates
-            .into_iter()
-            .for_each(|v| stacks[mv[2] - 1].push_back(v))
+    Ok(stacks)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+impl FromStr for Move {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_whitespace().collect_tuple() {
+            Some(("move", count, "from", from, "to", to)) => {
+                let stack_idx = |v: &str| -> Result<usize> {
+                    v.parse::<usize>()?
+                        .checked_sub(1)
+                        .ok_or_else(|| anyhow!("Stacks are numbered from 1 in move {s}"))
+                };
+                Ok(Move {
+                    count: count.parse()?,
+                    from: stack_idx(from)?,
+                    to: stack_idx(to)?,
+                })
+            }
+            _ => bail!("Could not parse move {s}"),
+        }
     }
 }
 
-fn parts(input: &str, p2: bool) -> String {
+trait Crane {
+    /// Reorders crates lifted off a stack (bottom crate first) into the order they end up on
+    /// the destination stack (bottom crate first)
+    fn arrange(&self, crates: &mut [char]);
+}
+
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn arrange(&self, crates: &mut [char]) {
+        crates.reverse()
+    }
+}
+
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn arrange(&self, _crates: &mut [char]) {}
+}
+
+fn do_one_move(mv: Move, stacks: &mut [VecDeque<char>], crane: &impl Crane) -> Result<()> {
+    let n_stacks = stacks.len();
+    if mv.from >= n_stacks || mv.to >= n_stacks {
+        bail!("{mv:?} refers to a stack outside of 1..={n_stacks}");
+    }
+    let from_stack = &mut stacks[mv.from];
+    let height = from_stack.len();
+    let remaining = height.checked_sub(mv.count).ok_or_else(|| {
+        anyhow!(
+            "{mv:?} lifts {} crates from stack {} which only has {height}",
+            mv.count,
+            mv.from + 1
+        )
+    })?;
+    let mut crates = from_stack.split_off(remaining);
+    crane.arrange(crates.make_contiguous());
+    stacks[mv.to].extend(crates);
+    Ok(())
+}
+
+fn render_stacks(stacks: &[VecDeque<char>]) -> String {
+    let height = stacks.iter().map(|v| v.len()).max().unwrap_or(0);
+    let mut lines: Vec<String> = (0..height)
+        .rev()
+        .map(|level| {
+            stacks
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("[{c}]"),
+                    None => "   ".into(),
+                })
+                .join(" ")
+        })
+        .collect();
+    lines.push((1..=stacks.len()).map(|idx| format!("{idx:^3}")).join(" "));
+    lines.join("\n")
+}
+
+/// Inverse of `parse_input`
+#[cfg(test)]
+fn write_input(stacks: &[VecDeque<char>], moves: &[Move]) -> String {
+    let mut out = render_stacks(stacks);
+    out.push_str("\n\n");
+    for mv in moves {
+        out.push_str(&format!("{mv}\n"));
+    }
+    out
+}
+
+fn parse_input(input: &str) -> Result<(Vec<VecDeque<char>>, Vec<Move>)> {
     let mut lines = input.lines();
-    let mut stacks = parse_stacks(&mut lines);
-    lines
-        .map(get_integers)
-        .for_each(|mv| do_one_move(mv, &mut stacks, !p2));
-    stacks
+    let stacks = parse_stacks(&mut lines)?;
+    let moves = lines
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<_>>()?;
+    Ok((stacks, moves))
+}
+
+/// Renders the stacks after each move, starting with the initial drawing
+fn render_moves(input: &str, crane: &impl Crane) -> Result<Vec<String>> {
+    let (mut stacks, moves) = parse_input(input)?;
+    let mut frames = vec![render_stacks(&stacks)];
+    for mv in moves {
+        do_one_move(mv, &mut stacks, crane)?;
+        frames.push(render_stacks(&stacks));
+    }
+    Ok(frames)
+}
+
+/// The CrateMover 9001 working through the moves
+pub fn animate(input: &str) -> Result<Vec<Frame>> {
+    Ok(render_moves(input, &CrateMover9001)?
         .into_iter()
-        .map(|mut v| v.pop_back().unwrap())
-        .collect()
+        .map(Frame::new)
+        .collect())
+}
+
+fn parts(input: &str, crane: &impl Crane) -> Result<String> {
+    let (mut stacks, moves) = parse_input(input)?;
+    for mv in moves {
+        do_one_move(mv, &mut stacks, crane)?;
+    }
+    Ok(stacks
+        .iter()
+        .map(|v| v.back().copied().unwrap_or(' '))
+        .collect())
 }
 
-fn part1(input: &str) -> String {
-    parts(input, false)
+fn part1(input: &str) -> Result<String> {
+    parts(input, &CrateMover9000)
 }
 
-fn part2(input: &str) -> String {
-    parts(input, true)
+fn part2(input: &str) -> Result<String> {
+    parts(input, &CrateMover9001)
 }
 
-pub fn main() -> std::io::Result<()> {
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(5)?;
-    dbg!(part1(input));
-    dbg!(part2(input));
+    dbg!(part1(input)?);
+    dbg!(part2(input)?);
 
     Ok(())
 }
@@ -93,12 +224,51 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2
     ";
-    let start = parse_stacks(&mut input.lines());
+    let start = parse_stacks(&mut input.lines()).unwrap();
     assert_eq!(start, vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]);
 }
 
 #[test]
-fn example() {
+fn task_render_roundtrip() -> Result<()> {
+    let input = &read_input_to_string(5)?;
+    let drawing = input.split("\n\n").next().unwrap();
+    let stacks = parse_stacks(&mut input.lines())?;
+    assert_eq!(render_stacks(&stacks), drawing);
+    Ok(())
+}
+
+#[test]
+fn roundtrip_random() -> Result<()> {
+    let mut rng = XorShift::new(5);
+    for _ in 0..500 {
+        let n_stacks = rng.below(12);
+        let stacks: Vec<VecDeque<char>> = (0..n_stacks)
+            .map(|_| {
+                (0..rng.below(8))
+                    .map(|_| (b'A' + rng.below(26) as u8) as char)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(parse_stacks(&mut render_stacks(&stacks).lines())?, stacks);
+
+        let moves = if n_stacks == 0 {
+            vec![]
+        } else {
+            (0..rng.below(5))
+                .map(|_| Move {
+                    count: rng.below(20),
+                    from: rng.below(n_stacks),
+                    to: rng.below(n_stacks),
+                })
+                .collect()
+        };
+        assert_eq!(parse_input(&write_input(&stacks, &moves))?, (stacks, moves));
+    }
+    Ok(())
+}
+
+#[test]
+fn example() -> Result<()> {
     let input = "    [D]    
 [N] [C]    
 [Z] [M] [P]
@@ -109,22 +279,129 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2
 ";
-    assert_eq!(part1(input), "CMZ");
-    assert_eq!(part2(input), "MCD");
+    assert_eq!(part1(input)?, "CMZ");
+    assert_eq!(part2(input)?, "MCD");
+    Ok(())
 }
 
 #[test]
-fn task() {
-    let input = &read_input_to_string(5).unwrap();
-    assert_eq!(part1(input), "LBLVVTVLP");
-    assert_eq!(part2(input), "TPFFBDRJD");
+fn example_animate() -> Result<()> {
+    let input = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+    1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+";
+    let frames = render_moves(input, &CrateMover9000)?;
+    assert_eq!(frames.len(), 5);
+    assert_eq!(
+        frames[0],
+        "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+    );
+    assert_eq!(
+        frames[2],
+        "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 "
+    );
+    assert_eq!(
+        frames[4],
+        "        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3 "
+    );
+    let frames = animate(input)?;
+    assert_eq!(frames.len(), 5);
+    assert_eq!(
+        frames[4].text,
+        "        [D]\n        [N]\n        [Z]\n[M] [C] [P]\n 1   2   3 "
+    );
+    Ok(())
+}
+
+/// Lifts at most `capacity` crates per trip, keeping the order within each trip
+#[cfg(test)]
+struct BatchCrane {
+    capacity: usize,
+}
+
+#[cfg(test)]
+impl Crane for BatchCrane {
+    fn arrange(&self, crates: &mut [char]) {
+        let trips = crates
+            .rchunks(self.capacity)
+            .flatten()
+            .copied()
+            .collect_vec();
+        crates.copy_from_slice(&trips);
+    }
+}
+
+#[test]
+fn example_custom_crane() -> Result<()> {
+    let input = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+    1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+";
+    assert_eq!(parts(input, &BatchCrane { capacity: 1 })?, part1(input)?);
+    assert_eq!(parts(input, &BatchCrane { capacity: 3 })?, part2(input)?);
+    assert_eq!(parts(input, &BatchCrane { capacity: 2 })?, "MCZ");
+    Ok(())
+}
+
+#[test]
+fn invalid_moves() {
+    let mut stacks = vec![VecDeque::from(['A', 'B']), VecDeque::from(['C'])];
+    assert!("move -1 from 1 to 2".parse::<Move>().is_err());
+    assert!("move 1 from 0 to 2".parse::<Move>().is_err());
+    assert!("move 1 from 1".parse::<Move>().is_err());
+    assert!(do_one_move(
+        "move 3 from 1 to 2".parse().unwrap(),
+        &mut stacks,
+        &CrateMover9000
+    )
+    .is_err());
+    assert!(do_one_move(
+        "move 1 from 1 to 3".parse().unwrap(),
+        &mut stacks,
+        &CrateMover9000
+    )
+    .is_err());
+    assert_eq!(
+        stacks,
+        vec![VecDeque::from(['A', 'B']), VecDeque::from(['C'])]
+    );
+    assert!(do_one_move(
+        "move 2 from 1 to 2".parse().unwrap(),
+        &mut stacks,
+        &CrateMover9000
+    )
+    .is_ok());
+    assert_eq!(
+        stacks,
+        vec![VecDeque::new(), VecDeque::from(['C', 'B', 'A'])]
+    );
+}
+
+#[test]
+fn task() -> Result<()> {
+    let input = &read_input_to_string(5)?;
+    assert_eq!(part1(input)?, "LBLVVTVLP");
+    assert_eq!(part2(input)?, "TPFFBDRJD");
+    Ok(())
 }
 
 #[bench]
 fn task_bench(b: &mut Bencher) {
     b.iter(|| {
         let input = &read_input_to_string(5).unwrap();
-        part1(input);
-        part2(input);
+        part1(input).unwrap();
+        part2(input).unwrap();
     })
 }
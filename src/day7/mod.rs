@@ -1,107 +1,244 @@
 extern crate test;
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    str::FromStr,
+};
 
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
 #[cfg(test)]
 use test::Bencher;
 
 use crate::utils::read_input_to_string;
 
-#[derive(Debug, Clone)]
-enum FileItem {
-    Dir(String),
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NodeKind {
+    Dir(BTreeMap<String, usize>),
     File(usize),
 }
 
-type FS = HashMap<String, (Option<String>, Vec<FileItem>)>;
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    kind: NodeKind,
+}
 
-fn get_parent(filename: Option<String>) -> Option<String> {
-    if let Some(filename) = filename {
-        filename.rsplit_once('/').map(|(parent, _)| parent.into())
-    } else {
-        None
-    }
+/// Directory tree reconstructed from a terminal transcript, nodes are stored in an arena
+/// where index 0 is the root and children always come after their parent
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileSystem {
+    nodes: Vec<Node>,
 }
 
-fn make_child(filename: Option<String>, next: &str) -> String {
-    if let Some(filename) = filename {
-        if filename == "/" {
-            format!("{filename}{next}")
-        } else {
-            format!("{filename}/{next}")
+impl FileSystem {
+    const ROOT: usize = 0;
+
+    fn new() -> Self {
+        FileSystem {
+            nodes: vec![Node {
+                name: "/".into(),
+                parent: None,
+                kind: NodeKind::Dir(BTreeMap::new()),
+            }],
         }
-    } else {
-        next.into()
     }
-}
 
-fn calc_sizes(dir: &FileItem, dir_sizes: &mut Vec<usize>, fs: &FS) -> usize {
-    match dir {
-        FileItem::File(size) => *size,
-        FileItem::Dir(dir) => {
-            let (_, children) = &fs[dir];
-            let size = children.iter().map(|v| calc_sizes(v, dir_sizes, fs)).sum();
-            dir_sizes.push(size);
-            size
+    fn children(&self, dir: usize) -> &BTreeMap<String, usize> {
+        match &self.nodes[dir].kind {
+            NodeKind::Dir(children) => children,
+            NodeKind::File(_) => panic!("{} is not a directory", self.path(dir)),
+        }
+    }
+
+    /// Returns the existing child with this name, so listing a directory twice is a no-op, but
+    /// fails if it is a file where a directory is expected or the other way around
+    fn add_child(&mut self, dir: usize, name: &str, kind: NodeKind) -> Result<usize> {
+        if let Some(&existing) = self.children(dir).get(name) {
+            let expected_dir = matches!(kind, NodeKind::Dir(_));
+            if self.is_dir(existing) != expected_dir {
+                bail!(
+                    "{} is listed both as a file and as a directory",
+                    self.path(existing)
+                );
+            }
+            return Ok(existing);
+        }
+        let idx = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.into(),
+            parent: Some(dir),
+            kind,
+        });
+        if let NodeKind::Dir(children) = &mut self.nodes[dir].kind {
+            children.insert(name.into(), idx);
+        }
+        Ok(idx)
+    }
+
+    fn is_dir(&self, idx: usize) -> bool {
+        matches!(self.nodes[idx].kind, NodeKind::Dir(_))
+    }
+
+    fn path(&self, idx: usize) -> String {
+        let mut parts = Vec::new();
+        let mut curr = Some(idx);
+        while let Some(i) = curr {
+            if i != Self::ROOT {
+                parts.push(self.nodes[i].name.as_str());
+            }
+            curr = self.nodes[i].parent;
+        }
+        parts.reverse();
+        format!("/{}", parts.join("/"))
+    }
+
+    /// Total size of every node, indexed like `nodes`
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                NodeKind::File(size) => size,
+                NodeKind::Dir(_) => 0,
+            })
+            .collect();
+        for (idx, node) in self.nodes.iter().enumerate().skip(1).rev() {
+            sizes[node.parent.unwrap()] += sizes[idx];
+        }
+        sizes
+    }
+
+    fn dir_sizes(&self) -> Vec<usize> {
+        let sizes = self.sizes();
+        (0..self.nodes.len())
+            .filter(|idx| self.is_dir(*idx))
+            .map(|idx| sizes[idx])
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    fn largest_dirs(&self, n: usize) -> Vec<(String, usize)> {
+        let sizes = self.sizes();
+        (0..self.nodes.len())
+            .filter(|idx| self.is_dir(*idx))
+            .sorted_by(|l, r| sizes[*r].cmp(&sizes[*l]).then(l.cmp(r)))
+            .take(n)
+            .map(|idx| (self.path(idx), sizes[idx]))
+            .collect()
+    }
+
+    /// Files whose name matches `pattern`, where `*` matches any run of characters and `?` a single one
+    #[allow(dead_code)]
+    fn find(&self, pattern: &str) -> Vec<(String, usize)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, node)| match node.kind {
+                NodeKind::File(size) if glob_match(pattern, &node.name) => {
+                    Some((self.path(idx), size))
+                }
+                _ => None,
+            })
+            .sorted()
+            .collect()
+    }
+
+    fn render_du(&self, dir: usize, sizes: &[usize], out: &mut Vec<String>) {
+        for &child in self.children(dir).values() {
+            if self.is_dir(child) {
+                self.render_du(child, sizes, out);
+            }
         }
+        out.push(format!("{}\t{}", sizes[dir], self.path(dir)));
+    }
+
+    /// Like `du`, one line per directory with subdirectories before their parent
+    #[allow(dead_code)]
+    fn du(&self) -> String {
+        let mut out = Vec::new();
+        self.render_du(Self::ROOT, &self.sizes(), &mut out);
+        out.join("\n")
+    }
+
+    fn render_tree(&self, idx: usize, depth: usize, out: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        let name = &self.nodes[idx].name;
+        match &self.nodes[idx].kind {
+            NodeKind::File(size) => out.push(format!("{indent}- {name} (file, size={size})")),
+            NodeKind::Dir(children) => {
+                out.push(format!("{indent}- {name} (dir)"));
+                for &child in children.values() {
+                    self.render_tree(child, depth + 1, out);
+                }
+            }
+        }
+    }
+
+    /// Same format as the listing in the puzzle description
+    #[allow(dead_code)]
+    fn tree(&self) -> String {
+        let mut out = Vec::new();
+        self.render_tree(Self::ROOT, 0, &mut out);
+        out.join("\n")
     }
 }
 
-fn build_fs(input: &str) -> FS {
-    // TODO: Make this not horrible
-    let mut line_iter = input.lines();
-    let mut current_dir: Option<String> = None;
-    let mut fs: FS = HashMap::new();
+impl FromStr for FileSystem {
+    type Err = anyhow::Error;
 
-    let mut maybe_line = line_iter.next();
-    while let Some(line) = maybe_line {
-        current_dir = if let Some(dir) = line.strip_prefix("$ cd ") {
-            match dir {
-                ".." => {
-                    maybe_line = line_iter.next();
-                    get_parent(current_dir)
+    fn from_str(input: &str) -> Result<Self> {
+        let mut fs = FileSystem::new();
+        let mut cwd = FileSystem::ROOT;
+
+        for line in input.lines() {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["$", "cd", "/"] => cwd = FileSystem::ROOT,
+                ["$", "cd", ".."] => cwd = fs.nodes[cwd].parent.unwrap_or(FileSystem::ROOT),
+                ["$", "cd", dir] => {
+                    cwd = fs.add_child(cwd, dir, NodeKind::Dir(BTreeMap::new()))?;
+                }
+                ["$", "ls"] => (),
+                ["dir", name] => {
+                    fs.add_child(cwd, name, NodeKind::Dir(BTreeMap::new()))?;
                 }
-                c => {
-                    line_iter.advance_by(1).unwrap(); // skip ls
-                    let next_dir = make_child(current_dir.clone(), c);
-
-                    let mut children: Vec<FileItem> = Vec::new();
-
-                    maybe_line = line_iter.next();
-                    while let Some(line) = maybe_line {
-                        if line.starts_with('$') {
-                            break;
-                        }
-                        match line.split_once(' ') {
-                            Some(("dir", name)) => {
-                                let child = make_child(Some(next_dir.clone()), name);
-                                children.push(FileItem::Dir(child));
-                            }
-                            Some((digits, name)) => {
-                                children.push(FileItem::File(digits.parse().unwrap_or_else(|_| {
-                                    panic!("could not parse {digits} as digit, for name {name}")
-                                })))
-                            }
-                            _ => panic!("could not split line {line}"),
-                        }
-                        maybe_line = line_iter.next();
-                    }
-                    fs.insert(next_dir.clone(), (current_dir, children.clone()));
-                    Some(next_dir)
+                [digits, name] => {
+                    let size = digits.parse().with_context(|| {
+                        format!("could not parse {digits} as digit, for name {name}")
+                    })?;
+                    fs.add_child(cwd, name, NodeKind::File(size))?;
                 }
+                _ => bail!("Unknown line {line}"),
             }
-        } else {
-            panic!("expected a cd command, got {line}");
         }
+        Ok(fs)
     }
-    fs
 }
 
-fn dir_sizes(input: &str) -> Vec<usize> {
-    let fs = build_fs(input);
-    let mut dir_sizes = Vec::new();
-    calc_sizes(&FileItem::Dir("/".into()), &mut dir_sizes, &fs);
-    dir_sizes
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matches[j]: pattern[..i] matches name[..j]
+    let mut matches = vec![false; name.len() + 1];
+    matches[0] = true;
+    for p in pattern {
+        let prev = matches.clone();
+        matches[0] = prev[0] && p == '*';
+        for j in 1..=name.len() {
+            matches[j] = match p {
+                '*' => prev[j] || matches[j - 1],
+                '?' => prev[j - 1],
+                c => prev[j - 1] && name[j - 1] == c,
+            };
+        }
+    }
+    matches[name.len()]
+}
+
+fn dir_sizes(input: &str) -> Result<Vec<usize>> {
+    Ok(input.parse::<FileSystem>()?.dir_sizes())
 }
 
 fn dir_sizes_map(input: &str) -> Vec<usize> {
@@ -130,9 +267,9 @@ fn dir_sizes_map(input: &str) -> Vec<usize> {
     fs.into_values().collect()
 }
 
-fn part1(input: &str) -> usize {
-    let dir_sizes = dir_sizes(input);
-    dir_sizes.iter().filter(|size| **size <= 100_000).sum()
+fn part1(input: &str) -> Result<usize> {
+    let dir_sizes = dir_sizes(input)?;
+    Ok(dir_sizes.iter().filter(|size| **size <= 100_000).sum())
 }
 
 fn part1_map(input: &str) -> usize {
@@ -140,18 +277,18 @@ fn part1_map(input: &str) -> usize {
     dir_sizes.iter().filter(|size| **size <= 100_000).sum()
 }
 
-fn part2(input: &str) -> usize {
+fn part2(input: &str) -> Result<usize> {
     const TOTAL_SPACE: usize = 70_000_000;
     const WANTED_SPACE: usize = 30_000_000;
 
-    let mut dir_sizes = dir_sizes(input);
+    let mut dir_sizes = dir_sizes(input)?;
     dir_sizes.sort();
     let used_space = &dir_sizes.last().unwrap();
 
-    *dir_sizes
+    Ok(*dir_sizes
         .iter()
         .find(|v| **v > (WANTED_SPACE - (TOTAL_SPACE - **used_space)))
-        .unwrap()
+        .unwrap())
 }
 
 fn part2_map(input: &str) -> usize {
@@ -168,11 +305,11 @@ fn part2_map(input: &str) -> usize {
         .unwrap()
 }
 
-pub fn main() -> std::io::Result<()> {
+pub fn main() -> Result<()> {
     let input = &read_input_to_string(7)?;
-    dbg!(part1(input));
+    dbg!(part1(input)?);
     dbg!(part1_map(input));
-    dbg!(part2(input));
+    dbg!(part2(input)?);
     dbg!(part2_map(input));
 
     Ok(())
@@ -203,18 +340,122 @@ $ ls
 8033020 d.log
 5626152 d.ext
 7214296 k";
-    assert_eq!(part1(input), 95437);
+    assert_eq!(part1(input).unwrap(), 95437);
     assert_eq!(part1_map(input), 95437);
-    assert_eq!(part2(input), 24933642);
+    assert_eq!(part2(input).unwrap(), 24933642);
     assert_eq!(part2_map(input), 24933642);
 }
 
+#[test]
+fn example_tree() {
+    let input = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+    let fs = input.parse::<FileSystem>().unwrap();
+    assert_eq!(
+        fs.tree(),
+        "
+- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        .trim()
+    );
+    assert_eq!(fs.du(), "584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/");
+    assert_eq!(
+        fs.largest_dirs(2),
+        vec![("/".into(), 48381165), ("/d".into(), 24933642)]
+    );
+    assert_eq!(
+        fs.find("d.*"),
+        vec![("/d/d.ext".into(), 5626152), ("/d/d.log".into(), 8033020)]
+    );
+    assert_eq!(
+        fs.find("?").into_iter().map(|(path, _)| path).collect_vec(),
+        vec!["/a/e/i", "/a/f", "/a/g", "/d/j", "/d/k"]
+    );
+}
+
+#[test]
+fn example_revisits() {
+    let input = "$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c
+$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c
+$ cd ..
+$ cd ..
+$ cd a";
+    let fs = input.parse::<FileSystem>().unwrap();
+    assert_eq!(
+        fs.tree(),
+        "- / (dir)\n  - a (dir)\n    - c (file, size=20)\n  - b (file, size=10)"
+    );
+    assert_eq!(fs.dir_sizes(), vec![30, 20]);
+}
+
+#[test]
+fn example_conflicts() {
+    for input in [
+        "$ cd /\n$ ls\n10 a\ndir a",
+        "$ cd /\n$ ls\ndir a\n10 a",
+        "$ cd /\n$ ls\n10 a\n$ cd a",
+    ] {
+        let err = input.parse::<FileSystem>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/a is listed both as a file and as a directory"
+        );
+    }
+    assert!("$ cd /\n$ ls\nx a".parse::<FileSystem>().is_err());
+}
+
 #[test]
 fn task() {
     let input = &read_input_to_string(7).unwrap();
-    assert_eq!(part1(input), 1844187);
+    assert_eq!(part1(input).unwrap(), 1844187);
     assert_eq!(part1_map(input), 1844187);
-    assert_eq!(part2(input), 4978279);
+    assert_eq!(part2(input).unwrap(), 4978279);
     assert_eq!(part2_map(input), 4978279);
 }
 
@@ -222,8 +463,8 @@ fn task() {
 fn task_bench(b: &mut Bencher) {
     let input = &read_input_to_string(7).unwrap();
     b.iter(|| {
-        part1(input);
-        part2(input);
+        part1(input).unwrap();
+        part2(input).unwrap();
     })
 }
 #[bench]
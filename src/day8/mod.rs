@@ -5,7 +5,12 @@ use ndarray::{s, Array2, Axis, FoldWhile, Zip};
 #[cfg(test)]
 use test::Bencher;
 
-use crate::utils::{read_input_to_string, rot90};
+#[cfg(test)]
+use crate::export::decode_png;
+use crate::{
+    export::{png, Palette},
+    utils::{read_input_to_string, rot90},
+};
 
 fn parse_input(input: &str) -> Array2<u8> {
     let board_width = input.lines().next().unwrap().len();
@@ -107,6 +112,12 @@ fn part2(input: &str) -> usize {
     *scenic_scores.iter().max().unwrap() as usize
 }
 
+/// Height map of the forest, taller trees lighter
+pub fn images(input: &str) -> Vec<(&'static str, Vec<u8>)> {
+    let palette = Palette::gradient(9, [20, 60, 20], [170, 230, 120]);
+    vec![("heights.png", png(&parse_input(input), &palette, 4))]
+}
+
 pub fn main() -> std::io::Result<()> {
     let input = &read_input_to_string(8)?;
     dbg!(part1(input));
@@ -133,6 +144,22 @@ fn example() {
     assert_eq!(part2(input), 8);
 }
 
+#[test]
+fn example_images() {
+    let input = "30373
+25512
+65332
+33549
+35390";
+    let images = images(input);
+    assert_eq!(images[0].0, "heights.png");
+    let ((width, height), pixels) = decode_png(&images[0].1);
+    assert_eq!((width, height), (20, 20));
+    // palette index is the height plus one, every tree four pixels wide
+    assert_eq!(pixels[..8], [4, 4, 4, 4, 1, 1, 1, 1]);
+    assert_eq!(pixels[19 * 20 + 16..], [1; 4]);
+}
+
 #[test]
 fn task() {
     let input = &read_input_to_string(8).unwrap();
@@ -0,0 +1,448 @@
+use std::time::Duration;
+
+use ndarray::Array2;
+
+pub(crate) type Rgb = [u8; 3];
+
+/// Colours for the values in a grid. Anything without a colour of its own gets `fallback`,
+/// which is also the background when frames of different sizes are combined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Palette<T> {
+    entries: Vec<(T, Rgb)>,
+    fallback: Rgb,
+}
+
+impl<T: PartialEq + Clone> Palette<T> {
+    pub(crate) fn new(entries: &[(T, Rgb)], fallback: Rgb) -> Self {
+        assert!(entries.len() < 256, "Images are limited to 256 colours");
+        Palette {
+            entries: entries.to_vec(),
+            fallback,
+        }
+    }
+
+    fn index(&self, value: &T) -> u8 {
+        self.entries
+            .iter()
+            .position(|(v, _)| v == value)
+            .map_or(0, |i| i as u8 + 1)
+    }
+
+    fn colours(&self) -> Vec<Rgb> {
+        let mut colours = vec![self.fallback];
+        colours.extend(self.entries.iter().map(|(_, rgb)| *rgb));
+        colours
+    }
+}
+
+impl Palette<u8> {
+    /// `0..=max` fading from `from` to `to`
+    pub(crate) fn gradient(max: u8, from: Rgb, to: Rgb) -> Self {
+        let entries = (0..=max)
+            .map(|v| {
+                let t = v as f64 / max.max(1) as f64;
+                let mix = |c: usize| (from[c] as f64 + (to[c] as f64 - from[c] as f64) * t) as u8;
+                (v, [mix(0), mix(1), mix(2)])
+            })
+            .collect::<Vec<_>>();
+        Palette::new(&entries, [0, 0, 0])
+    }
+}
+
+/// Palette indices of a grid with every cell blown up to `scale` by `scale` pixels, padded
+/// with the fallback colour up to `(height, width)` pixels
+fn indexed<T: PartialEq + Clone>(
+    grid: &Array2<T>,
+    palette: &Palette<T>,
+    scale: usize,
+    (height, width): (usize, usize),
+) -> Vec<u8> {
+    let mut pixels = vec![0; height * width];
+    for ((row, col), value) in grid.indexed_iter() {
+        let index = palette.index(value);
+        for y in row * scale..(row + 1) * scale {
+            pixels[y * width + col * scale..y * width + (col + 1) * scale].fill(index);
+        }
+    }
+    pixels
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// zlib stream made of stored deflate blocks, so no compression but also no dependency
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Paletted PNG of `grid`, each cell `scale` pixels wide
+pub(crate) fn png<T: PartialEq + Clone>(
+    grid: &Array2<T>,
+    palette: &Palette<T>,
+    scale: usize,
+) -> Vec<u8> {
+    let (height, width) = (grid.nrows() * scale, grid.ncols() * scale);
+    let pixels = indexed(grid, palette, scale, (height, width));
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::new();
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // 8 bit palette indices, default compression, filtering and no interlacing
+    header.extend([8, 3, 0, 0, 0]);
+    png_chunk(&mut out, b"IHDR", &header);
+    png_chunk(&mut out, b"PLTE", &palette.colours().concat());
+
+    let mut rows = Vec::with_capacity(height * (width + 1));
+    for row in pixels.chunks(width.max(1)) {
+        rows.push(0);
+        rows.extend(row);
+    }
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&rows));
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Literal codes sent between clear codes. The decoder grows its table by one entry per
+/// code and would switch to 10 bit codes at 512 entries, so stay clear of that
+const GIF_RUN: usize = 250;
+
+/// LZW image data that only ever uses 9 bit literal codes, again trading size for simplicity
+fn gif_lzw(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u32 = 256;
+    const END: u32 = 257;
+    let mut bytes = Vec::new();
+    let (mut bits, mut n_bits) = (0u32, 0);
+    let mut emit = |code: u32| {
+        bits |= code << n_bits;
+        n_bits += 9;
+        while n_bits >= 8 {
+            bytes.push(bits as u8);
+            bits >>= 8;
+            n_bits -= 8;
+        }
+    };
+    for run in pixels.chunks(GIF_RUN) {
+        emit(CLEAR);
+        run.iter().for_each(|p| emit(*p as u32));
+    }
+    emit(END);
+    if n_bits > 0 {
+        bytes.push(bits as u8);
+    }
+
+    let mut out = vec![8];
+    for block in bytes.chunks(255) {
+        out.push(block.len() as u8);
+        out.extend(block);
+    }
+    out.push(0);
+    out
+}
+
+/// Looping animated GIF of `frames`, each cell `scale` pixels wide. Smaller frames are padded
+/// on the bottom and right to the size of the largest one
+pub(crate) fn gif<T: PartialEq + Clone>(
+    frames: &[Array2<T>],
+    palette: &Palette<T>,
+    scale: usize,
+    delay: Duration,
+) -> Vec<u8> {
+    let height = frames.iter().map(|f| f.nrows()).max().unwrap_or(0) * scale;
+    let width = frames.iter().map(|f| f.ncols()).max().unwrap_or(0) * scale;
+    let size = |pixels: usize| {
+        u16::try_from(pixels)
+            .unwrap_or_else(|_| panic!("GIFs are at most 65535 pixels across, not {pixels}"))
+            .to_le_bytes()
+    };
+
+    let mut out = b"GIF89a".to_vec();
+    out.extend(size(width));
+    out.extend(size(height));
+    // 256 colour global table, background is the fallback colour
+    out.extend([0xF7, 0, 0]);
+    let mut colours = palette.colours();
+    colours.resize(256, [0, 0, 0]);
+    out.extend(colours.concat());
+    // loop forever
+    out.extend(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+    let centis = (delay.as_millis() / 10) as u16;
+    for frame in frames {
+        out.extend([0x21, 0xF9, 4, 0x04]);
+        out.extend(centis.to_le_bytes());
+        out.extend([0, 0]);
+
+        out.push(0x2C);
+        out.extend([0, 0, 0, 0]);
+        out.extend(size(width));
+        out.extend(size(height));
+        out.push(0);
+        out.extend(gif_lzw(&indexed(frame, palette, scale, (height, width))));
+    }
+    out.push(0x3B);
+    out
+}
+
+#[cfg(test)]
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut i = 2;
+    loop {
+        let last = zlib[i] == 1;
+        let len = u16::from_le_bytes([zlib[i + 1], zlib[i + 2]]) as usize;
+        data.extend(&zlib[i + 5..i + 5 + len]);
+        i += 5 + len;
+        if last {
+            break;
+        }
+    }
+    assert_eq!(zlib[i..], adler32(&data).to_be_bytes());
+    data
+}
+
+/// Reads a variable width LZW stream, the way a GIF viewer would
+#[cfg(test)]
+fn gif_unlzw(data: &[u8]) -> Vec<u8> {
+    let min_size = data[0] as u32;
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut i = 1;
+    while data[i] != 0 {
+        bytes.extend(&data[i + 1..i + 1 + data[i] as usize]);
+        i += 1 + data[i] as usize;
+    }
+
+    let (clear, end) = (1 << min_size, (1 << min_size) + 1);
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let mut size = min_size + 1;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    let (mut bits, mut n_bits) = (0u32, 0);
+    let mut bytes = bytes.into_iter();
+    loop {
+        while n_bits < size {
+            bits |= (bytes.next().unwrap() as u32) << n_bits;
+            n_bits += 8;
+        }
+        let code = bits & ((1 << size) - 1);
+        bits >>= size;
+        n_bits -= size;
+
+        if code == clear {
+            table = (0..clear).map(|c| vec![c as u8]).collect();
+            table.extend([vec![], vec![]]);
+            size = min_size + 1;
+            prev = None;
+            continue;
+        }
+        if code == end {
+            return out;
+        }
+        let entry = match (table.get(code as usize), &prev) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(prev)) => [prev.clone(), vec![prev[0]]].concat(),
+            (None, None) => panic!("bad code {code}"),
+        };
+        if let Some(prev) = prev {
+            table.push([prev, vec![entry[0]]].concat());
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        out.extend(&entry);
+        prev = Some(entry);
+    }
+}
+
+/// Kind and data of every chunk, checking the signature and CRCs on the way
+#[cfg(test)]
+fn png_chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut i = 8;
+    while i < png.len() {
+        let len = u32::from_be_bytes(png[i..i + 4].try_into().unwrap()) as usize;
+        let crc = u32::from_be_bytes(png[i + 8 + len..i + 12 + len].try_into().unwrap());
+        assert_eq!(crc32(&png[i + 4..i + 8 + len]), crc);
+        chunks.push((&png[i + 4..i + 8], &png[i + 8..i + 8 + len]));
+        i += 12 + len;
+    }
+    chunks
+}
+
+/// `(width, height)` and palette indices of a PNG written by `png`
+#[cfg(test)]
+pub(crate) fn decode_png(png: &[u8]) -> ((usize, usize), Vec<u8>) {
+    let chunks = png_chunks(png);
+    let kinds = chunks.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+    assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+    let header = chunks[0].1;
+    let width = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+
+    let rows = inflate_stored(chunks[2].1);
+    assert_eq!(rows.len(), height * (width + 1));
+    let pixels = rows
+        .chunks(width + 1)
+        .flat_map(|row| {
+            assert_eq!(row[0], 0, "unfiltered rows");
+            row[1..].to_vec()
+        })
+        .collect();
+    ((width, height), pixels)
+}
+
+/// `(width, height)` and palette indices of every frame of a GIF written by `gif`
+#[cfg(test)]
+pub(crate) fn decode_gif(gif: &[u8]) -> ((usize, usize), Vec<Vec<u8>>) {
+    assert_eq!(gif[..6], *b"GIF89a");
+    let width = u16::from_le_bytes([gif[6], gif[7]]) as usize;
+    let height = u16::from_le_bytes([gif[8], gif[9]]) as usize;
+    assert_eq!(*gif.last().unwrap(), 0x3B);
+
+    // skip the header, colour table and loop extension to the first frame
+    let mut i = 13 + 768 + 19;
+    let mut frames = Vec::new();
+    while gif[i] == 0x21 {
+        i += 8;
+        assert_eq!(gif[i], 0x2C);
+        assert_eq!(
+            gif[i + 5..i + 9],
+            [gif[6], gif[7], gif[8], gif[9]],
+            "frames cover the whole image"
+        );
+        i += 10;
+        let pixels = gif_unlzw(&gif[i..]);
+        assert_eq!(pixels.len(), width * height);
+        i += 1;
+        while gif[i] != 0 {
+            i += 1 + gif[i] as usize;
+        }
+        i += 1;
+        frames.push(pixels);
+    }
+    assert_eq!(i, gif.len() - 1);
+    ((width, height), frames)
+}
+
+#[test]
+fn checksums() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    let data = (0..200_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    assert_eq!(inflate_stored(&zlib_stored(&data)), data);
+    assert!(inflate_stored(&zlib_stored(&[])).is_empty());
+}
+
+#[test]
+fn png_layout() {
+    let grid = Array2::from_shape_vec((2, 3), "#.o..#".chars().collect()).unwrap();
+    let palette = Palette::new(&[('#', [90, 90, 90]), ('o', [230, 190, 90])], [0, 0, 0]);
+    let png = png(&grid, &palette, 2);
+
+    let chunks = png_chunks(&png);
+    let kinds = chunks.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+    assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 6, 0, 0, 0, 4, 8, 3, 0, 0, 0]);
+    assert_eq!(chunks[1].1, [0, 0, 0, 90, 90, 90, 230, 190, 90]);
+    assert_eq!(
+        inflate_stored(chunks[2].1),
+        [
+            [0, 1, 1, 0, 0, 2, 2],
+            [0, 1, 1, 0, 0, 2, 2],
+            [0, 0, 0, 0, 0, 1, 1],
+            [0, 0, 0, 0, 0, 1, 1]
+        ]
+        .concat()
+    );
+    assert_eq!(
+        decode_png(&png),
+        (
+            (6, 4),
+            [[1, 1, 0, 0, 2, 2].repeat(2), [0, 0, 0, 0, 1, 1].repeat(2)].concat()
+        )
+    );
+}
+
+#[test]
+fn gif_frames() {
+    let small = Array2::from_elem((1, 2), 3u8);
+    let large = Array2::from_shape_fn((30, 40), |(r, c)| ((r * 40 + c) % 10) as u8);
+    let palette = Palette::gradient(9, [0, 0, 0], [0, 180, 0]);
+    let gif = gif(
+        &[small, large.clone()],
+        &palette,
+        1,
+        Duration::from_millis(50),
+    );
+
+    assert_eq!(gif[..6], *b"GIF89a");
+    assert_eq!(gif[6..10], [40, 0, 30, 0]);
+    assert_eq!(gif[13 + 3..13 + 6], [0, 0, 0]);
+    assert_eq!(gif[13 + 30..13 + 33], [0, 180, 0]);
+    // delay of the first frame, after the header, colour table and loop extension
+    assert_eq!(gif[13 + 768 + 19 + 4..13 + 768 + 19 + 6], [5, 0]);
+
+    let (size, frames) = decode_gif(&gif);
+    assert_eq!(size, (40, 30));
+    assert_eq!(frames.len(), 2);
+    let mut first = vec![0; 1200];
+    first[..2].fill(4);
+    assert_eq!(frames[0], first);
+    assert_eq!(frames[1], large.iter().map(|v| v + 1).collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic(expected = "GIFs are at most 65535 pixels across, not 70000")]
+fn gif_too_wide() {
+    let wide = Array2::from_elem((1, 35_000), 0u8);
+    gif(
+        &[wide],
+        &Palette::gradient(1, [0; 3], [255; 3]),
+        2,
+        Duration::ZERO,
+    );
+}
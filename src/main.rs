@@ -6,9 +6,12 @@ use std::fs::File;
 use std::io::{ErrorKind, Write};
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Utc};
 use curl::easy::Easy;
+mod animate;
+mod coverage;
+mod cycle;
 mod day1;
 mod day10;
 mod day11;
@@ -34,6 +37,7 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod export;
 mod utils;
 
 const TOKEN: &str = "";
@@ -117,8 +121,66 @@ fn make_until_today() -> Result<()> {
     (1..day + 1).try_for_each(|x| make_day(year, x))
 }
 
+/// Plays back the simulations of the chosen days, or every day that has one
+fn animate_days(options: animate::Options) -> Result<()> {
+    let days = if options.days.is_empty() {
+        vec![5, 14, 17, 23, 24]
+    } else {
+        options.days
+    };
+    let commands = animate::stdin_commands();
+    for day in days {
+        let input = &utils::read_input_to_string(day)?;
+        let frames = match day {
+            5 => day5::animate(input)?,
+            14 => day14::animate(input),
+            17 => day17::animate(input),
+            23 => day23::animate(input),
+            24 => day24::animate(input),
+            _ => bail!("Day {day} has no animation"),
+        };
+        options
+            .player
+            .play(&frames, &mut std::io::stdout(), &commands)?;
+    }
+    Ok(())
+}
+
+/// Writes the images and other exports of every day that has some into `dir`
+fn export_days(dir: &str) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for day in [8, 12, 13, 14, 16, 18, 21, 23] {
+        let input = &utils::read_input_to_string(day)?;
+        let images = match day {
+            8 => day8::images(input),
+            12 => day12::images(input),
+            13 => day13::exports(input)?,
+            14 => day14::images(input),
+            16 => day16::exports(input),
+            18 => day18::images(input),
+            21 => day21::exports(input)?,
+            23 => day23::images(input),
+            _ => unreachable!(),
+        };
+        for (name, bytes) in images {
+            let mut path = PathBuf::from(dir);
+            path.push(format!("day{day}-{name}"));
+            fs::write(path, bytes)?;
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     make_until_today()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(options) = animate::Options::from_args(&args)? {
+        return animate_days(options);
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--export") {
+        let dir = args.get(i + 1).context("--export needs a directory")?;
+        return export_days(dir);
+    }
     day1::main()?;
     day2::main()?;
     day3::main()?;
@@ -130,11 +192,11 @@ fn main() -> Result<()> {
     day9::main()?;
     day10::main()?;
     day11::main()?;
-    // day12::main()?;
+    day12::main()?;
     day13::main()?;
     day14::main()?;
     day15::main()?;
-    // day16::main()?;
+    day16::main()?;
     day17::main()?;
     day18::main()?;
     day19::main()?;